@@ -1,5 +1,5 @@
 use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
-use onebrc::byte_buffer::ByteBuffer;
+use onebrc::byte_buffer::{ByteBuffer, Delimiters};
 
 fn bench_byte_position(c: &mut Criterion) {
     let mut group = c.benchmark_group("byte_position");
@@ -84,7 +84,14 @@ fn bench_find_delimiters(c: &mut Criterion) {
     for (name, window) in &test_cases {
         group.throughput(Throughput::Bytes(window.len() as u64));
         group.bench_with_input(BenchmarkId::new("swar", name), window, |b, window| {
-            b.iter(|| black_box(window.as_slice()).find_delimiters())
+            b.iter(|| black_box(window.as_slice()).find_delimiters(Delimiters::default()))
+        });
+        group.bench_with_input(BenchmarkId::new("sse2", name), window, |b, window| {
+            b.iter(|| black_box(window.as_slice()).find_delimiters_sse2(Delimiters::default()))
+        });
+        #[cfg(feature = "portable-simd")]
+        group.bench_with_input(BenchmarkId::new("simd", name), window, |b, window| {
+            b.iter(|| black_box(window.as_slice()).find_delimiters_simd(Delimiters::default()))
         });
     }
 
@@ -102,7 +109,7 @@ fn bench_find_delimiters_window_sizes(c: &mut Criterion) {
         group.bench_with_input(
             BenchmarkId::new("swar", size),
             &full[..size],
-            |b, window| b.iter(|| black_box(window).find_delimiters()),
+            |b, window| b.iter(|| black_box(window).find_delimiters(Delimiters::default())),
         );
     }
 
@@ -122,7 +129,14 @@ fn bench_find_delimiters_delimiter_density(c: &mut Criterion) {
     for (name, window) in &cases {
         group.throughput(Throughput::Bytes(32));
         group.bench_with_input(BenchmarkId::new("swar", name), window, |b, window| {
-            b.iter(|| black_box(window.as_slice()).find_delimiters())
+            b.iter(|| black_box(window.as_slice()).find_delimiters(Delimiters::default()))
+        });
+        group.bench_with_input(BenchmarkId::new("sse2", name), window, |b, window| {
+            b.iter(|| black_box(window.as_slice()).find_delimiters_sse2(Delimiters::default()))
+        });
+        #[cfg(feature = "portable-simd")]
+        group.bench_with_input(BenchmarkId::new("simd", name), window, |b, window| {
+            b.iter(|| black_box(window.as_slice()).find_delimiters_simd(Delimiters::default()))
         });
     }
 
@@ -143,7 +157,7 @@ fn bench_find_delimiters_realistic(c: &mut Criterion) {
         b.iter(|| {
             let start = i % (lines.len().saturating_sub(32));
             let end = (start + 32).min(lines.len());
-            let result = black_box(&lines[start..end]).find_delimiters();
+            let result = black_box(&lines[start..end]).find_delimiters(Delimiters::default());
             i += 15; // Advance by ~1 line
             result
         })
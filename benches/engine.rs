@@ -0,0 +1,80 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use onebrc::engine::{self, Backend, Config};
+use onebrc::hash_table::Tracking;
+use pprof::criterion::{Output, PProfProfiler};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::{fmt::Write, fs::read_to_string, hint::black_box};
+
+/// `rows` rows of the 1BRC format, from the stations in `data/weather_stations.csv`.
+fn measurements(rows: usize) -> String {
+    let names: Vec<_> = read_to_string("data/weather_stations.csv")
+        .expect("could not read data/weather_stations.csv")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split_once(';').unwrap().0.to_string())
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut out = String::with_capacity(rows * 16);
+
+    for _ in 0..rows {
+        let name = &names[rng.random_range(0..names.len())];
+        let tenths: i32 = rng.random_range(-999..=999);
+        let sign = if tenths < 0 { "-" } else { "" };
+        let tenths = tenths.abs();
+        writeln!(out, "{name};{sign}{}.{}", tenths / 10, tenths % 10).unwrap();
+    }
+
+    out
+}
+
+/// The default config, `Tenths` rows of `name;temp` with nothing tracked, on every backend the
+/// CPU supports, against the same rows read one at a time because squares are tracked.
+fn bench_aggregate(c: &mut Criterion) {
+    let input = measurements(1_000_000);
+
+    let mut group = c.benchmark_group("aggregate");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(20);
+
+    for backend in [Backend::Avx512, Backend::Avx2, Backend::Sse2] {
+        if !backend.is_supported() {
+            continue;
+        }
+
+        let config = Config {
+            backend,
+            ..Config::default()
+        };
+
+        group.bench_with_input(
+            BenchmarkId::new("default", format!("{backend:?}")),
+            &config,
+            |b, config| b.iter(|| engine::aggregate_with(black_box(input.as_bytes()), config)),
+        );
+    }
+
+    let tracked = Config {
+        tracking: Tracking {
+            squares: true,
+            ..Tracking::default()
+        },
+        ..Config::default()
+    };
+
+    group.bench_with_input(
+        BenchmarkId::new("squares", format!("{:?}", tracked.backend)),
+        &tracked,
+        |b, config| b.iter(|| engine::aggregate_with(black_box(input.as_bytes()), config)),
+    );
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_aggregate
+}
+
+criterion_main!(benches);
@@ -57,7 +57,7 @@ fn bench_lookup_collisions(c: &mut Criterion) {
         b.iter(|| table.lookup(black_box(*hit_hash_1), black_box(*hit_prefix_1)))
     });
 
-    group.bench_function(&format!("hit_depth_{}", max_depth), |b| {
+    group.bench_function(format!("hit_depth_{}", max_depth), |b| {
         b.iter(|| table.lookup(black_box(*hit_hash_max), black_box(*hit_prefix_max)))
     });
 
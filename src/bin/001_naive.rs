@@ -1,8 +1,10 @@
+use onebrc::Error;
 use std::{
     collections::HashMap,
     env,
     fs::File,
     io::{BufRead, BufReader},
+    process::ExitCode,
 };
 
 struct Stats {
@@ -25,22 +27,50 @@ impl Stats {
     }
 }
 
-fn main() {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Error> {
     let filename = env::args().nth(1).expect("expected filename argument");
 
-    let file = File::open(filename).unwrap();
+    let file = File::open(filename)?;
     let reader = BufReader::new(file);
     let mut stations: HashMap<String, Stats> = HashMap::new();
+    let mut offset = 0;
 
-    for line in reader.lines() {
-        let line = line.unwrap();
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let mut line = line?;
+        let (row_offset, row_line) = (offset, i + 1);
+        offset += line.len() + 1;
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        let line = String::from_utf8(line).map_err(|_| Error::InvalidUtf8 {
+            offset: row_offset,
+            line: row_line,
+        })?;
 
         if line.starts_with('#') {
             continue;
         }
 
-        let (name, temp) = line.split_once(';').unwrap();
-        let temp: f64 = temp.parse().unwrap();
+        let (name, temp) = line.split_once(';').ok_or(Error::MissingSeparator {
+            offset: row_offset,
+            line: row_line,
+        })?;
+        let temp: f64 = temp.parse().map_err(|_| Error::BadTemperature {
+            offset: row_offset,
+            line: row_line,
+        })?;
 
         if let Some(station) = stations.get_mut(name) {
             station.min = temp.min(station.min);
@@ -69,4 +99,6 @@ fn main() {
         );
     }
     println!("}}");
+
+    Ok(())
 }
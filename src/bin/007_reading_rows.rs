@@ -1,4 +1,4 @@
-use onebrc::byte_buffer::ByteBuffer;
+use onebrc::byte_buffer::{ByteBuffer, Delimiters};
 use onebrc::hash_table::Table;
 use std::{
     env,
@@ -65,7 +65,7 @@ fn main() -> io::Result<()> {
             let end = start + 32.min(buf_end - start);
             let window = &effective_buf[start..end];
 
-            let (mut semicolons, mut newlines) = window.find_delimiters();
+            let (mut semicolons, mut newlines) = window.find_delimiters(Delimiters::default());
 
             if newlines == 0 {
                 let semicolon_pos = effective_buf[start..].byte_position(b';').unwrap();
@@ -1,4 +1,4 @@
-use onebrc::byte_buffer::ByteBuffer;
+use onebrc::byte_buffer::{ByteBuffer, Delimiters};
 use onebrc::hash_table::Table;
 use std::{
     env,
@@ -11,8 +11,8 @@ const MAGIC_MULTIPLIER: u64 = 100 * 0x1000000 + 10 * 0x10000 + 1;
 
 #[inline(always)]
 fn parse_temp(bytes: &[u8]) -> i16 {
-    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() } as u64;
-    let n = n & (1 << (bytes.len() * 8)) - 1;
+    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() };
+    let n = n & ((1 << (bytes.len() * 8)) - 1);
 
     let dot = (!n & DOT_BITS).trailing_zeros();
     let sign = (((!n) << 59) as i64 >> 63) as u64;
@@ -59,7 +59,7 @@ fn main() -> io::Result<()> {
             let end = start + 32.min(buf_end - start);
             let window = &effective_buf[start..end];
 
-            let (mut semicolons, mut newlines) = window.find_delimiters();
+            let (mut semicolons, mut newlines) = window.find_delimiters(Delimiters::default());
 
             if newlines == 0 {
                 let semicolon_pos = effective_buf[start..].byte_position(b';').unwrap();
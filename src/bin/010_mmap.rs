@@ -1,5 +1,5 @@
 use memmap2::Mmap;
-use onebrc::byte_buffer::ByteBuffer;
+use onebrc::byte_buffer::{ByteBuffer, Delimiters};
 use onebrc::hash_table::Table;
 use std::{
     env,
@@ -12,8 +12,8 @@ const MAGIC_MULTIPLIER: u64 = 100 * 0x1000000 + 10 * 0x10000 + 1;
 
 #[inline(always)]
 fn parse_temp(bytes: &[u8]) -> i16 {
-    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() } as u64;
-    let n = n & (1 << (bytes.len() * 8)) - 1;
+    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() };
+    let n = n & ((1 << (bytes.len() * 8)) - 1);
 
     let dot = (!n & DOT_BITS).trailing_zeros();
     let sign = (((!n) << 59) as i64 >> 63) as u64;
@@ -62,9 +62,9 @@ fn main() -> io::Result<()> {
         let window_b = &region_b[cursor_b..end_b];
         let window_c = &region_c[cursor_c..end_c];
 
-        let (mut semi_a, mut nl_a) = window_a.find_delimiters();
-        let (mut semi_b, mut nl_b) = window_b.find_delimiters();
-        let (mut semi_c, mut nl_c) = window_c.find_delimiters();
+        let (mut semi_a, mut nl_a) = window_a.find_delimiters(Delimiters::default());
+        let (mut semi_b, mut nl_b) = window_b.find_delimiters(Delimiters::default());
+        let (mut semi_c, mut nl_c) = window_c.find_delimiters(Delimiters::default());
 
         if nl_a == 0 {
             cursor_a = process_long_line(region_a, &mut tbl, cursor_a, region_a.len());
@@ -187,7 +187,7 @@ fn cleanup_region(tbl: &mut Table, region: &[u8], cursor: usize) {
     while cursor < region.len() {
         let end_a = cursor + 32.min(region.len() - cursor);
         let window_a = &region[cursor..end_a];
-        let (mut semi_a, mut nl_a) = window_a.find_delimiters();
+        let (mut semi_a, mut nl_a) = window_a.find_delimiters(Delimiters::default());
 
         if nl_a == 0 {
             cursor = process_long_line(region, tbl, cursor, region.len());
@@ -1,242 +1,173 @@
 use memmap2::Mmap;
-use onebrc::byte_buffer::ByteBuffer;
-use onebrc::hash_table::Table;
+use onebrc::{
+    Error,
+    engine::{self, Config, Policy, snapshot},
+    output::{self, Format},
+};
 use std::{
     env,
-    fs::File,
-    io::{self},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+    thread,
 };
 
-const DOT_BITS: u64 = 0x10101000;
-const MAGIC_MULTIPLIER: u64 = 100 * 0x1000000 + 10 * 0x10000 + 1;
-
-#[inline(always)]
-fn parse_temp(bytes: &[u8]) -> i16 {
-    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() } as u64;
-    let n = n & (1 << (bytes.len() * 8)) - 1;
-
-    let dot = (!n & DOT_BITS).trailing_zeros();
-    let sign = (((!n) << 59) as i64 >> 63) as u64;
-    let mask = !(sign & 0xff);
-    let digits = ((n & mask) << (28 - dot)) & 0xf000f0f00;
-    let abs = ((digits * MAGIC_MULTIPLIER) >> 32) & 0x3FF;
-    ((abs ^ sign).wrapping_sub(sign)) as i16
-}
-
-fn main() -> io::Result<()> {
-    let filename = env::args().nth(1).expect("expected filename argument");
-
-    let file = File::open(filename).unwrap();
-    let buf = unsafe { Mmap::map(&file)? };
-
-    let mut tbl: Table = Table::new(1 << 16);
-
-    let last_newline = buf.iter().rposition(|&b| b == b'\n').unwrap_or(0);
-    let effective_buf = &buf[..last_newline];
-    let buf_end = effective_buf.len();
-
-    let q1 = effective_buf[..buf_end / 3]
-        .iter()
-        .rposition(|&b| b == b'\n')
-        .unwrap_or(0);
-
-    let q2 = effective_buf[..(buf_end / 3) * 2]
-        .iter()
-        .rposition(|&b| b == b'\n')
-        .unwrap_or(0);
-
-    let region_a = &effective_buf[..q1];
-    let region_b = &effective_buf[q1 + 1..q2];
-    let region_c = &effective_buf[q2 + 1..buf_end];
-
-    let mut cursor_a = 0;
-    let mut cursor_b = 0;
-    let mut cursor_c = 0;
-
-    while cursor_a < region_a.len() && cursor_b < region_b.len() && cursor_c < region_c.len() {
-        let end_a = cursor_a + 64.min(region_a.len() - cursor_a);
-        let end_b = cursor_b + 64.min(region_b.len() - cursor_b);
-        let end_c = cursor_c + 64.min(region_c.len() - cursor_c);
-
-        let window_a = &region_a[cursor_a..end_a];
-        let window_b = &region_b[cursor_b..end_b];
-        let window_c = &region_c[cursor_c..end_c];
-
-        let (mut semi_a, mut nl_a) = window_a.find_delimiters64();
-        let (mut semi_b, mut nl_b) = window_b.find_delimiters64();
-        let (mut semi_c, mut nl_c) = window_c.find_delimiters64();
-
-        if nl_a == 0 {
-            cursor_a = process_long_line(region_a, &mut tbl, cursor_a, region_a.len());
-            continue;
-        }
-
-        if nl_b == 0 {
-            cursor_b = process_long_line(region_b, &mut tbl, cursor_b, region_b.len());
-            continue;
-        }
-
-        if nl_c == 0 {
-            cursor_c = process_long_line(region_c, &mut tbl, cursor_c, region_c.len());
-            continue;
-        }
-
-        let mut line_cursor_a = 0;
-        let mut line_cursor_b = 0;
-        let mut line_cursor_c = 0;
-
-        while nl_a != 0 && nl_b != 0 && nl_c != 0 {
-            let semi_pos_a = semi_a.trailing_zeros() as usize;
-            let semi_pos_b = semi_b.trailing_zeros() as usize;
-            let semi_pos_c = semi_c.trailing_zeros() as usize;
-            let nl_pos_a = nl_a.trailing_zeros() as usize;
-            let nl_pos_b = nl_b.trailing_zeros() as usize;
-            let nl_pos_c = nl_c.trailing_zeros() as usize;
-
-            let name_a = unsafe { window_a.get_unchecked(line_cursor_a..semi_pos_a) };
-            let name_b = unsafe { window_b.get_unchecked(line_cursor_b..semi_pos_b) };
-            let name_c = unsafe { window_c.get_unchecked(line_cursor_c..semi_pos_c) };
-            let temp_a = unsafe { window_a.get_unchecked(semi_pos_a + 1..nl_pos_a) };
-            let temp_b = unsafe { window_b.get_unchecked(semi_pos_b + 1..nl_pos_b) };
-            let temp_c = unsafe { window_c.get_unchecked(semi_pos_c + 1..nl_pos_c) };
-
-            let (hash_a, prefix_a) = Table::hash(name_a);
-            let (hash_b, prefix_b) = Table::hash(name_b);
-            let (hash_c, prefix_c) = Table::hash(name_c);
-
-            tbl.prefetch(hash_a);
-            tbl.prefetch(hash_b);
-            tbl.prefetch(hash_c);
-
-            let parsed_temp_a = parse_temp(temp_a);
-            let parsed_temp_b = parse_temp(temp_b);
-            let parsed_temp_c = parse_temp(temp_c);
-
-            let slot_a = tbl.lookup(hash_a, prefix_a);
-            let slot_b = tbl.lookup(hash_b, prefix_b);
-            let slot_c = tbl.lookup(hash_c, prefix_c);
-
-            tbl.update(slot_a, hash_a, prefix_a, name_a, parsed_temp_a);
-            tbl.update(slot_b, hash_b, prefix_b, name_b, parsed_temp_b);
-            tbl.update(slot_c, hash_c, prefix_c, name_c, parsed_temp_c);
-
-            semi_a &= semi_a - 1;
-            semi_b &= semi_b - 1;
-            semi_c &= semi_c - 1;
-
-            nl_a &= nl_a - 1;
-            nl_b &= nl_b - 1;
-            nl_c &= nl_c - 1;
-
-            line_cursor_a = nl_pos_a + 1;
-            line_cursor_b = nl_pos_b + 1;
-            line_cursor_c = nl_pos_c + 1;
-        }
-
-        while nl_a != 0 {
-            line_cursor_a = process_line(window_a, &mut tbl, line_cursor_a, semi_a, nl_a);
-            semi_a &= semi_a - 1;
-            nl_a &= nl_a - 1;
-        }
-
-        while nl_b != 0 {
-            line_cursor_b = process_line(window_b, &mut tbl, line_cursor_b, semi_b, nl_b);
-            semi_b &= semi_b - 1;
-            nl_b &= nl_b - 1;
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
         }
+    }
+}
 
-        while nl_c != 0 {
-            line_cursor_c = process_line(window_c, &mut tbl, line_cursor_c, semi_c, nl_c);
-            semi_c &= semi_c - 1;
-            nl_c &= nl_c - 1;
+fn run() -> Result<(), Error> {
+    let mut filename = None;
+    let mut quarantine_path = None;
+    let mut snapshot_path = None;
+    let mut format = Format::Text;
+    #[cfg(feature = "arrow")]
+    let mut arrow_path = None;
+    #[cfg(feature = "parquet")]
+    let mut parquet_path = None;
+    let mut config = Config {
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        ..Config::default()
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => {
+                config.threads = args
+                    .next()
+                    .expect("expected value for --threads")
+                    .parse()
+                    .expect("expected int argument for --threads");
+            }
+            "--on-error" => {
+                config.policy = match args.next().as_deref() {
+                    Some("strict") => Policy::Strict,
+                    Some("skip") => Policy::Skip,
+                    _ => panic!("expected strict or skip for --on-error"),
+                };
+            }
+            "--decimals" => {
+                config.precision.decimals = args
+                    .next()
+                    .expect("expected value for --decimals")
+                    .parse()
+                    .expect("expected int argument for --decimals");
+            }
+            "--int-digits" => {
+                config.precision.int_digits = args
+                    .next()
+                    .expect("expected value for --int-digits")
+                    .parse()
+                    .expect("expected int argument for --int-digits");
+            }
+            "--separator" => {
+                config.delimiters.separator = match args.next().as_deref().map(str::as_bytes) {
+                    Some(b"tab" | b"\\t") => b'\t',
+                    Some(&[b]) => b,
+                    _ => panic!("expected a single byte, or tab, for --separator"),
+                };
+            }
+            "--terminator" => {
+                config.delimiters.terminator = match args.next().as_deref().map(str::as_bytes) {
+                    Some(b"\\n") => b'\n',
+                    Some(b"\\r") => b'\r',
+                    Some(&[b]) => b,
+                    _ => panic!("expected a single byte, \\n or \\r, for --terminator"),
+                };
+            }
+            "--crlf" => config.delimiters.crlf = true,
+            "--percentiles" => config.tracking.histograms = true,
+            "--spread" => config.tracking.squares = true,
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    Some("csv") => Format::Csv,
+                    Some("ndjson") => Format::Ndjson,
+                    _ => panic!("expected text, json, csv or ndjson for --format"),
+                };
+            }
+            "--columns" => {
+                config.schema.columns = args
+                    .next()
+                    .expect("expected value for --columns")
+                    .parse()
+                    .expect("expected int argument for --columns");
+            }
+            "--key" => {
+                let columns = args.next().expect("expected value for --key");
+                config.schema.keys =
+                    parse_columns(&columns).expect("expected comma-separated ints for --key");
+            }
+            "--value" => {
+                let columns = args.next().expect("expected value for --value");
+                config.schema.values =
+                    parse_columns(&columns).expect("expected comma-separated ints for --value");
+            }
+            #[cfg(feature = "arrow")]
+            "--arrow" => arrow_path = Some(args.next().expect("expected path for --arrow")),
+            #[cfg(feature = "parquet")]
+            "--parquet" => parquet_path = Some(args.next().expect("expected path for --parquet")),
+            "--snapshot" => {
+                snapshot_path = Some(args.next().expect("expected path for --snapshot"));
+            }
+            "--quarantine" => {
+                config.policy = Policy::Quarantine;
+                quarantine_path = Some(args.next().expect("expected path for --quarantine"));
+            }
+            _ => filename = Some(arg),
         }
-
-        cursor_a += line_cursor_a;
-        cursor_b += line_cursor_b;
-        cursor_c += line_cursor_c;
     }
 
-    cleanup_region(&mut tbl, region_a, cursor_a);
-    cleanup_region(&mut tbl, region_b, cursor_b);
-    cleanup_region(&mut tbl, region_c, cursor_c);
+    let filename = filename.expect("expected filename argument");
 
-    let mut entries = tbl.entries();
+    let file = File::open(filename)?;
+    let buf = unsafe { Mmap::map(&file)? };
 
-    entries.sort_unstable_by_key(|(name, station)| &name[..station.len as usize]);
+    let results = engine::aggregate_with(&buf, &config)?;
+    output::write(&results, format.formatter(), &mut io::stdout().lock())?;
 
-    print!("{{");
-    for (i, (name, entry)) in entries.iter().enumerate() {
-        let name = str::from_utf8(&name[..entry.len as usize]).unwrap();
-        let separator = if i != entries.len() - 1 { ", " } else { "" };
-        print!(
-            "{name}={min:.1}/{mean:.1}/{max:.1}{separator}",
-            min = entry.min as f64 / 10.0,
-            mean = (entry.sum as f64 / entry.count as f64) / 10.0,
-            max = entry.max as f64 / 10.0
-        );
+    if let Some(path) = snapshot_path {
+        fs::write(path, snapshot::encode(&results))?;
     }
-    println!("}}");
-
-    Ok(())
-}
-
-#[inline(always)]
-fn cleanup_region(tbl: &mut Table, region: &[u8], cursor: usize) {
-    let mut cursor = cursor;
 
-    while cursor < region.len() {
-        let end_a = cursor + 64.min(region.len() - cursor);
-        let window_a = &region[cursor..end_a];
-        let (mut semi_a, mut nl_a) = window_a.find_delimiters64();
-
-        if nl_a == 0 {
-            cursor = process_long_line(region, tbl, cursor, region.len());
-            continue;
-        }
+    #[cfg(feature = "arrow")]
+    if let Some(path) = arrow_path {
+        output::arrow::write_ipc(&results, BufWriter::new(File::create(path)?))?;
+    }
 
-        let mut line_cursor_a = 0;
+    #[cfg(feature = "parquet")]
+    if let Some(path) = parquet_path {
+        output::parquet::write_parquet(&results, BufWriter::new(File::create(path)?))?;
+    }
 
-        while nl_a != 0 {
-            line_cursor_a = process_line(window_a, tbl, line_cursor_a, semi_a, nl_a);
-            semi_a &= semi_a - 1;
-            nl_a &= nl_a - 1;
+    if let Some(path) = quarantine_path {
+        let mut out = BufWriter::new(File::create(path)?);
+        for rejected in results.quarantined() {
+            write!(out, "{}\t", rejected.error.offset().unwrap())?;
+            out.write_all(&rejected.row)?;
+            out.write_all(b"\n")?;
         }
-
-        cursor += line_cursor_a;
+        out.flush()?;
     }
-}
-
-#[inline(always)]
-fn process_long_line(buf: &[u8], tbl: &mut Table, start: usize, end: usize) -> usize {
-    let semi_pos = buf[start..].byte_position(b';').unwrap();
-    let nl_pos = buf[start + semi_pos + 1..]
-        .byte_position(b'\n')
-        .unwrap_or(end - (start + semi_pos + 1));
 
-    let name = &buf[start..start + semi_pos];
-    let temp = parse_temp(&buf[start + semi_pos + 1..start + semi_pos + 1 + nl_pos]);
-
-    let (hash, prefix) = Table::hash(name);
-    let slot = tbl.lookup(hash, prefix);
-    tbl.update(slot, hash, prefix, name, temp);
+    if results.skipped() > 0 {
+        eprintln!("skipped {} malformed rows", results.skipped());
+    }
 
-    start + semi_pos + 1 + nl_pos + 1
+    Ok(())
 }
 
-#[inline(always)]
-fn process_line(buf: &[u8], tbl: &mut Table, start: usize, semi: u64, nl: u64) -> usize {
-    let semi_pos = semi.trailing_zeros() as usize;
-    let nl_pos = nl.trailing_zeros() as usize;
-
-    let name = unsafe { buf.get_unchecked(start..semi_pos) };
-    let temp = unsafe { buf.get_unchecked(semi_pos + 1..nl_pos) };
-    let (hash, prefix) = Table::hash(name);
-    tbl.prefetch(hash);
-
-    let parsed_temp = parse_temp(temp);
-
-    let slot = tbl.lookup(hash, prefix);
-    tbl.update(slot, hash, prefix, name, parsed_temp);
-
-    nl_pos + 1
+/// Parses a comma-separated list of 0-based columns into a `Schema` bitmask.
+fn parse_columns(columns: &str) -> Option<u64> {
+    columns.split(',').try_fold(0u64, |mask, column| {
+        Some(mask | 1u64.checked_shl(column.parse().ok()?)?)
+    })
 }
@@ -1,11 +1,16 @@
 use std::env;
 use std::fs;
-use std::str::FromStr;
+use std::process::ExitCode;
+
+use onebrc::Error;
 
 use rand::Rng;
 use rand_distr::Distribution;
 use rand_distr::Normal;
 
+/// The stations to sample, one `name;mean` per line.
+const STATIONS: &str = "data/weather_stations.csv";
+
 #[derive(Debug)]
 struct City {
     name: String,
@@ -22,24 +27,32 @@ impl City {
     }
 
     fn sample(&self, rng: &mut impl Rng) -> f64 {
-        self.distribution.sample(rng).max(-99.9).min(99.9)
+        self.distribution.sample(rng).clamp(-99.9, 99.9)
     }
-}
-
-#[derive(Debug)]
-struct ParseCityError;
-
-impl FromStr for City {
-    type Err = ParseCityError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (name, mean_str) = s.split_once(';').ok_or(ParseCityError)?;
-        let mean: f64 = mean_str.parse().map_err(|_| ParseCityError)?;
+    /// Parses a `name;mean` line of the stations file, starting at `offset` on line `line`.
+    fn parse(s: &str, offset: usize, line: usize) -> Result<Self, Error> {
+        let (name, mean_str) = s
+            .split_once(';')
+            .ok_or(Error::MissingSeparator { offset, line })?;
+        let mean: f64 = mean_str
+            .parse()
+            .map_err(|_| Error::BadTemperature { offset, line })?;
         Ok(City::new(name, mean))
     }
 }
 
-fn main() {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {STATIONS}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Error> {
     let num: u32 = env::args()
         .nth(1)
         .expect("single argument n expected")
@@ -47,15 +60,21 @@ fn main() {
         .parse()
         .expect("expected int argument");
 
-    let cities: Vec<City> = fs::read_to_string("data/weather_stations.csv")
-        .expect("could not read weather_data.csv")
-        .lines()
-        .filter(|line| !line.starts_with('#'))
-        .map(|line| {
-            line.parse()
-                .unwrap_or_else(|_| panic!("failed to parse: {}", line))
-        })
-        .collect();
+    let stations = fs::read_to_string(STATIONS)?;
+
+    let mut cities: Vec<City> = vec![];
+    let mut offset = 0;
+    for (i, line) in stations.split('\n').enumerate() {
+        let row_offset = offset;
+        offset += line.len() + 1;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        cities.push(City::parse(line, row_offset, i + 1)?);
+    }
 
     let mut rng = rand::rng();
 
@@ -68,4 +87,6 @@ fn main() {
         }
         println!("{}", lines.join("\n"));
     }
+
+    Ok(())
 }
@@ -0,0 +1,57 @@
+use onebrc::{
+    Error,
+    engine::snapshot,
+    output::{self, Format},
+};
+use std::{env, fs, io, process::ExitCode};
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Merges snapshots written with `avx512 --snapshot`, printing the combined results and
+/// optionally writing them to a new snapshot with `--output`.
+fn run() -> Result<(), Error> {
+    let mut paths = Vec::new();
+    let mut output_path = None;
+    let mut format = Format::Text;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output_path = Some(args.next().expect("expected path for --output")),
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    Some("csv") => Format::Csv,
+                    Some("ndjson") => Format::Ndjson,
+                    _ => panic!("expected text, json, csv or ndjson for --format"),
+                };
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    let first = paths.next().expect("expected snapshot arguments");
+
+    let mut results = snapshot::decode(&fs::read(first)?)?;
+    for path in paths {
+        results.merge(snapshot::decode(&fs::read(path)?)?)?;
+    }
+
+    output::write(&results, format.formatter(), &mut io::stdout().lock())?;
+
+    if let Some(path) = output_path {
+        fs::write(path, snapshot::encode(&results))?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use hashbrown::HashMap;
 use onebrc::hash_table::Table;
-use std::{fs::read_to_string, u64};
+use std::fs::read_to_string;
 
 fn main() {
     let mut names: Vec<_> = read_to_string("data/weather_stations.csv")
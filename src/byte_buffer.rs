@@ -1,17 +1,71 @@
 const MSB_MASK: u64 = 0x8080_8080_8080_8080;
 const LSB_MASK: u64 = 0x0101_0101_0101_0101;
-const SEMICOLON_MASK: u64 = LSB_MASK * (b';' as u64);
-const NEWLINE_MASK: u64 = LSB_MASK * (b'\n' as u64);
+
+/// The bytes separating fields within a record, and ending each record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delimiters {
+    pub separator: u8,
+    pub terminator: u8,
+    /// Whether records may also end in `\r` before the terminator, which is then stripped. Only
+    /// used by the engine: scanners report terminators alone.
+    pub crlf: bool,
+}
+
+impl Delimiters {
+    /// Whether the scanners can find these delimiters. Windows are padded with zeroes, so
+    /// neither may be zero, and they must differ.
+    pub fn is_supported(self) -> bool {
+        self.separator != 0 && self.terminator != 0 && self.separator != self.terminator
+    }
+}
+
+impl Default for Delimiters {
+    /// `name;temp\n`, as in the 1BRC.
+    fn default() -> Self {
+        Self {
+            separator: b';',
+            terminator: b'\n',
+            crlf: false,
+        }
+    }
+}
 
 pub trait ByteBuffer {
     fn byte_position(&self, needle: u8) -> Option<usize>;
 
-    fn find_delimiters(&self) -> (u32, u32);
+    /// Picks the fastest implementation the CPU supports at runtime.
+    fn find_delimiters(&self, delims: Delimiters) -> (u32, u32);
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    unsafe fn find_delimiters_avx2(&self, delims: Delimiters) -> (u32, u32);
+
+    /// Two 16-byte compares. SSE2 is part of the x86_64 baseline.
+    fn find_delimiters_sse2(&self, delims: Delimiters) -> (u32, u32);
+
+    /// `std::simd` implementation, for targets without a dedicated one. Requires nightly.
+    #[cfg(feature = "portable-simd")]
+    fn find_delimiters_simd(&self, delims: Delimiters) -> (u32, u32);
+
+    fn find_delimiters_swar(&self, delims: Delimiters) -> (u32, u32);
+
+    /// Picks the fastest implementation the CPU supports at runtime.
+    fn find_delimiters64(&self, delims: Delimiters) -> (u64, u64);
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F and AVX-512BW.
+    unsafe fn find_delimiters64_avx512(&self, delims: Delimiters) -> (u64, u64);
 
-    fn find_delimiters_swar(&self) -> (u32, u32);
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    unsafe fn find_delimiters64_avx2(&self, delims: Delimiters) -> (u64, u64);
 
-    #[cfg(all(target_feature = "avx512f", target_feature = "avx512bw"))]
-    fn find_delimiters64(&self) -> (u64, u64);
+    fn find_delimiters64_sse2(&self, delims: Delimiters) -> (u64, u64);
+
+    fn find_delimiters64_swar(&self, delims: Delimiters) -> (u64, u64);
 }
 
 impl ByteBuffer for [u8] {
@@ -44,90 +98,95 @@ impl ByteBuffer for [u8] {
     }
 
     #[inline(always)]
-    #[cfg(all(target_feature = "avx512f", target_feature = "avx512bw"))]
-    fn find_delimiters64(&self) -> (u64, u64) {
-        let (semi, nl) = unsafe {
-            #[cfg(target_arch = "x86")]
-            use std::arch::x86::{
-                __m512i, _mm512_cmpeq_epi8_mask, _mm512_loadu_si512, _mm512_set1_epi8,
-            };
-
-            #[cfg(target_arch = "x86_64")]
-            use std::arch::x86_64::{
-                __m512i, _mm512_cmpeq_epi8_mask, _mm512_loadu_si512, _mm512_set1_epi8,
-            };
-
-            let chunk = _mm512_loadu_si512(self[..64.min(self.len())].as_ptr() as *const __m512i);
-            let semi = _mm512_set1_epi8(b';' as i8);
-            let nl = _mm512_set1_epi8(b'\n' as i8);
-            let semi_mask = _mm512_cmpeq_epi8_mask(chunk, semi);
-            let nl_mask = _mm512_cmpeq_epi8_mask(chunk, nl);
-
-            (semi_mask, nl_mask)
-        };
+    fn find_delimiters64(&self, delims: Delimiters) -> (u64, u64) {
+        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+            return unsafe { self.find_delimiters64_avx512(delims) };
+        }
+
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { self.find_delimiters64_avx2(delims) };
+        }
 
-        (semi, nl)
+        self.find_delimiters64_sse2(delims)
     }
 
     #[inline(always)]
-    #[cfg(target_feature = "avx2")]
-    fn find_delimiters(&self) -> (u32, u32) {
-        let (semicolons, newlines) = unsafe {
-            #[cfg(target_arch = "x86")]
-            use std::arch::x86::{
-                __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8,
-                _mm256_set1_epi8,
-            };
-
-            #[cfg(target_arch = "x86_64")]
-            use std::arch::x86_64::{
-                __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8,
-                _mm256_set1_epi8,
-            };
-
-            let a = _mm256_loadu_si256(self[0..32.min(self.len())].as_ptr() as *const __m256i);
-            let b = _mm256_set1_epi8(b';' as i8);
-            let result = _mm256_cmpeq_epi8(a, b);
-            let semicolons = _mm256_movemask_epi8(result);
-
-            let a = _mm256_loadu_si256(self[0..32.min(self.len())].as_ptr() as *const __m256i);
-            let b = _mm256_set1_epi8(b'\n' as i8);
-            let result = _mm256_cmpeq_epi8(a, b);
-            let newlines = _mm256_movemask_epi8(result);
-
-            (semicolons, newlines)
-        };
+    unsafe fn find_delimiters64_avx512(&self, delims: Delimiters) -> (u64, u64) {
+        match self.first_chunk::<64>() {
+            Some(window) => unsafe { scan64_avx512(window, delims) },
+            None => unsafe { scan64_avx512(&pad(self), delims) },
+        }
+    }
 
-        let valid_mask = if self.len() >= 32 {
-            0xFFFFFFFF
-        } else {
-            (1u32 << self.len()) - 1
-        };
+    #[inline(always)]
+    unsafe fn find_delimiters64_avx2(&self, delims: Delimiters) -> (u64, u64) {
+        widen(self, |half| unsafe { half.find_delimiters_avx2(delims) })
+    }
 
-        (semicolons as u32 & valid_mask, newlines as u32 & valid_mask)
+    #[inline(always)]
+    fn find_delimiters64_sse2(&self, delims: Delimiters) -> (u64, u64) {
+        widen(self, |half| half.find_delimiters_sse2(delims))
     }
 
-    #[cfg(not(target_feature = "avx2"))]
-    fn find_delimiters(&self) -> (u32, u32) {
-        self.find_delimiters_swar()
+    #[inline(always)]
+    fn find_delimiters64_swar(&self, delims: Delimiters) -> (u64, u64) {
+        widen(self, |half| half.find_delimiters_swar(delims))
     }
 
-    fn find_delimiters_swar(&self) -> (u32, u32) {
+    #[inline(always)]
+    fn find_delimiters(&self, delims: Delimiters) -> (u32, u32) {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { self.find_delimiters_avx2(delims) };
+        }
+
+        self.find_delimiters_sse2(delims)
+    }
+
+    #[inline(always)]
+    fn find_delimiters_sse2(&self, delims: Delimiters) -> (u32, u32) {
+        match self.first_chunk::<32>() {
+            Some(window) => scan32_sse2(window, delims),
+            None => scan32_sse2(&pad(self), delims),
+        }
+    }
+
+    #[cfg(feature = "portable-simd")]
+    #[inline(always)]
+    fn find_delimiters_simd(&self, delims: Delimiters) -> (u32, u32) {
+        use std::simd::{cmp::SimdPartialEq, u8x32};
+
+        let chunk = u8x32::load_or_default(&self[..32.min(self.len())]);
+        let semicolons = chunk.simd_eq(u8x32::splat(delims.separator)).to_bitmask() as u32;
+        let newlines = chunk.simd_eq(u8x32::splat(delims.terminator)).to_bitmask() as u32;
+
+        (semicolons, newlines)
+    }
+
+    #[inline(always)]
+    unsafe fn find_delimiters_avx2(&self, delims: Delimiters) -> (u32, u32) {
+        match self.first_chunk::<32>() {
+            Some(window) => unsafe { scan32_avx2(window, delims) },
+            None => unsafe { scan32_avx2(&pad(self), delims) },
+        }
+    }
+
+    fn find_delimiters_swar(&self, delims: Delimiters) -> (u32, u32) {
         let mut i = 0;
 
+        let separator_mask = LSB_MASK * delims.separator as u64;
+        let terminator_mask = LSB_MASK * delims.terminator as u64;
+
         let mut semicolons = 0u32;
         let mut newlines = 0u32;
 
         while i + 8 <= self.len().min(32) {
             let chunk = unsafe { (self[i..i + 8].as_ptr() as *const u64).read_unaligned() };
 
-            let semicolon_diff = chunk ^ SEMICOLON_MASK;
-            let newline_diff = chunk ^ NEWLINE_MASK;
+            let semicolon_diff = chunk ^ separator_mask;
+            let newline_diff = chunk ^ terminator_mask;
 
-            let mut semicolon_matches =
-                semicolon_diff.wrapping_sub(LSB_MASK) & !semicolon_diff & MSB_MASK;
-            let mut newline_matches =
-                newline_diff.wrapping_sub(LSB_MASK) & !newline_diff & MSB_MASK;
+            let mut semicolon_matches = zero_bytes(semicolon_diff);
+            let mut newline_matches = zero_bytes(newline_diff);
 
             let offset = i as u32;
 
@@ -145,10 +204,10 @@ impl ByteBuffer for [u8] {
         }
 
         while i < self.len().min(32) {
-            match self[i] {
-                b';' => semicolons |= 1 << i,
-                b'\n' => newlines |= 1 << i,
-                _ => (),
+            if self[i] == delims.separator {
+                semicolons |= 1 << i;
+            } else if self[i] == delims.terminator {
+                newlines |= 1 << i;
             }
 
             i += 1;
@@ -158,9 +217,127 @@ impl ByteBuffer for [u8] {
     }
 }
 
+/// Sets the high bit of exactly the zero bytes of `x`. Unlike `x - LSB_MASK & !x`, a zero byte
+/// never flags a `0x01` byte above it, which would be the byte after a match: `-` after `,`.
+#[inline(always)]
+fn zero_bytes(x: u64) -> u64 {
+    !(((x & !MSB_MASK) + !MSB_MASK) | x) & MSB_MASK
+}
+
+/// Copies a short window into a zeroed buffer, so full-width loads stay in bounds. Zeroes never
+/// match a supported delimiter.
+#[inline(always)]
+fn pad<const N: usize>(buf: &[u8]) -> [u8; N] {
+    let mut padded = [0u8; N];
+    padded[..buf.len()].copy_from_slice(buf);
+    padded
+}
+
+/// # Safety
+///
+/// The CPU must support AVX-512F and AVX-512BW.
+#[inline(always)]
+unsafe fn scan64_avx512(window: &[u8; 64], delims: Delimiters) -> (u64, u64) {
+    unsafe {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86::{
+            __m512i, _mm512_cmpeq_epi8_mask, _mm512_loadu_si512, _mm512_set1_epi8,
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64::{
+            __m512i, _mm512_cmpeq_epi8_mask, _mm512_loadu_si512, _mm512_set1_epi8,
+        };
+
+        let chunk = _mm512_loadu_si512(window.as_ptr() as *const __m512i);
+        let semi = _mm512_set1_epi8(delims.separator as i8);
+        let nl = _mm512_set1_epi8(delims.terminator as i8);
+        let semi_mask = _mm512_cmpeq_epi8_mask(chunk, semi);
+        let nl_mask = _mm512_cmpeq_epi8_mask(chunk, nl);
+
+        (semi_mask, nl_mask)
+    }
+}
+
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[inline(always)]
+unsafe fn scan32_avx2(window: &[u8; 32], delims: Delimiters) -> (u32, u32) {
+    unsafe {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86::{
+            __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8, _mm256_set1_epi8,
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64::{
+            __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8, _mm256_set1_epi8,
+        };
+
+        let a = _mm256_loadu_si256(window.as_ptr() as *const __m256i);
+        let b = _mm256_set1_epi8(delims.separator as i8);
+        let result = _mm256_cmpeq_epi8(a, b);
+        let semicolons = _mm256_movemask_epi8(result);
+
+        let b = _mm256_set1_epi8(delims.terminator as i8);
+        let result = _mm256_cmpeq_epi8(a, b);
+        let newlines = _mm256_movemask_epi8(result);
+
+        (semicolons as u32, newlines as u32)
+    }
+}
+
+#[inline(always)]
+fn scan32_sse2(window: &[u8; 32], delims: Delimiters) -> (u32, u32) {
+    unsafe {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86::{
+            __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64::{
+            __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+        };
+
+        let ptr = window.as_ptr() as *const __m128i;
+        let lo = _mm_loadu_si128(ptr);
+        let hi = _mm_loadu_si128(ptr.add(1));
+
+        let semi = _mm_set1_epi8(delims.separator as i8);
+        let semicolons = _mm_movemask_epi8(_mm_cmpeq_epi8(lo, semi)) as u32
+            | (_mm_movemask_epi8(_mm_cmpeq_epi8(hi, semi)) as u32) << 16;
+
+        let nl = _mm_set1_epi8(delims.terminator as i8);
+        let newlines = _mm_movemask_epi8(_mm_cmpeq_epi8(lo, nl)) as u32
+            | (_mm_movemask_epi8(_mm_cmpeq_epi8(hi, nl)) as u32) << 16;
+
+        (semicolons, newlines)
+    }
+}
+
+/// Builds 64-byte masks from two 32-byte scans.
+#[inline(always)]
+fn widen(buf: &[u8], find: impl Fn(&[u8]) -> (u32, u32)) -> (u64, u64) {
+    let (semi_lo, nl_lo) = find(buf);
+
+    if buf.len() <= 32 {
+        return (semi_lo as u64, nl_lo as u64);
+    }
+
+    let (semi_hi, nl_hi) = find(&buf[32..]);
+
+    (
+        semi_lo as u64 | ((semi_hi as u64) << 32),
+        nl_lo as u64 | ((nl_hi as u64) << 32),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::Guarded;
 
     fn fmt_to_nibbles(n: u32) -> String {
         let bitstr = format!("{:0>32b}", n);
@@ -187,22 +364,190 @@ mod tests {
             last_newline &= last_newline - 1;
         }
 
-        return last_newline.trailing_zeros() as usize + 1;
+        last_newline.trailing_zeros() as usize + 1
     }
 
     #[test]
     fn test_find_delimiters_equivalence() {
         let bytes = b";12.3\nAbc;4.5\nDef;6.7\nGhi;8.9\nJon;3.3\n";
 
-        let (semicolons1, newlines1) = bytes[..32].find_delimiters();
-        let (semicolons2, newlines2) = bytes[..32].find_delimiters_swar();
+        let (semicolons1, newlines1) = bytes[..32].find_delimiters(Delimiters::default());
+        let (semicolons2, newlines2) = bytes[..32].find_delimiters_swar(Delimiters::default());
         assert_eq!(semicolons1, semicolons2);
         assert_eq!(newlines1, newlines2);
     }
 
+    #[test]
+    fn test_find_delimiters_sse2_equivalence() {
+        let bytes = b"Hamburg;12.0\nBulawayo;8.9\nSt. John's;15.2\nXi;1.2\nBo;3.4\n";
+
+        for start in 0..bytes.len() - 32 {
+            for len in 0..=32 {
+                let window = &bytes[start..start + len];
+                assert_eq!(
+                    window.find_delimiters_sse2(Delimiters::default()),
+                    window.find_delimiters_swar(Delimiters::default()),
+                    "start = {start}, len = {len}"
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "portable-simd")]
+    #[test]
+    fn test_find_delimiters_simd_equivalence() {
+        let bytes = b"Hamburg;12.0\nBulawayo;8.9\nSt. John's;15.2\nXi;1.2\nBo;3.4\n";
+
+        for start in 0..bytes.len() - 32 {
+            for len in 0..=32 {
+                let window = &bytes[start..start + len];
+                assert_eq!(
+                    window.find_delimiters_simd(Delimiters::default()),
+                    window.find_delimiters_swar(Delimiters::default()),
+                    "start = {start}, len = {len}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_delimiters64_equivalence() {
+        let bytes = b"Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nSt. John's;15.2\nCracow;12.6\nXi";
+
+        for len in 0..=64 {
+            let window = &bytes[..len];
+            let expected = window.find_delimiters64_swar(Delimiters::default());
+
+            assert_eq!(
+                window.find_delimiters64(Delimiters::default()),
+                expected,
+                "len = {len}"
+            );
+            assert_eq!(
+                window.find_delimiters64_sse2(Delimiters::default()),
+                expected
+            );
+
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(
+                    unsafe { window.find_delimiters64_avx2(Delimiters::default()) },
+                    expected
+                );
+            }
+
+            if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+                assert_eq!(
+                    unsafe { window.find_delimiters64_avx512(Delimiters::default()) },
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_delimiters_custom_equivalence() {
+        let bytes = b"Ab,-1.0\r\nHamburg,-12.0\r\nBulawayo\t8.9\r\nPalembang;38.8\r\nSt. John's,15.2\nCracow\t12.6\r\nXi";
+        let delims = [
+            Delimiters {
+                separator: b',',
+                ..Delimiters::default()
+            },
+            Delimiters {
+                separator: b'\t',
+                terminator: b'\r',
+                crlf: false,
+            },
+        ];
+
+        for delims in delims {
+            for len in 0..=64 {
+                let window = &bytes[..len];
+                let (semi, nl) = window.find_delimiters64_swar(delims);
+
+                for (i, &b) in window.iter().enumerate() {
+                    assert_eq!(semi >> i & 1 == 1, b == delims.separator, "len = {len}");
+                    assert_eq!(nl >> i & 1 == 1, b == delims.terminator, "len = {len}");
+                }
+
+                assert_eq!(window.find_delimiters64(delims), (semi, nl));
+                assert_eq!(window.find_delimiters64_sse2(delims), (semi, nl));
+
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(unsafe { window.find_delimiters64_avx2(delims) }, (semi, nl));
+                }
+
+                if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+                    assert_eq!(
+                        unsafe { window.find_delimiters64_avx512(delims) },
+                        (semi, nl)
+                    );
+                }
+
+                let window = &window[..len.min(32)];
+                let expected = window.find_delimiters_swar(delims);
+                assert_eq!(window.find_delimiters(delims), expected);
+                assert_eq!(window.find_delimiters_sse2(delims), expected);
+
+                #[cfg(feature = "portable-simd")]
+                assert_eq!(window.find_delimiters_simd(delims), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_delimiters_at_end_of_mapping() {
+        let bytes = b"Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nSt. John's;15.2\nCracow;12.6\nXi";
+
+        for len in 0..=64 {
+            let guarded = Guarded::new(&bytes[bytes.len() - len..]);
+            let window = guarded.as_slice();
+            let expected = window.find_delimiters64_swar(Delimiters::default());
+
+            assert_eq!(
+                window.find_delimiters64(Delimiters::default()),
+                expected,
+                "len = {len}"
+            );
+            assert_eq!(
+                window.find_delimiters64_sse2(Delimiters::default()),
+                expected
+            );
+            assert_eq!(
+                window.find_delimiters(Delimiters::default()),
+                window.find_delimiters_swar(Delimiters::default())
+            );
+            assert_eq!(
+                window.find_delimiters_sse2(Delimiters::default()),
+                window.find_delimiters_swar(Delimiters::default())
+            );
+            assert_eq!(
+                window.byte_position(b'\n'),
+                window.iter().position(|&b| b == b'\n')
+            );
+
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(
+                    unsafe { window.find_delimiters64_avx2(Delimiters::default()) },
+                    expected
+                );
+                assert_eq!(
+                    unsafe { window.find_delimiters_avx2(Delimiters::default()) },
+                    window.find_delimiters_swar(Delimiters::default())
+                );
+            }
+
+            if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+                assert_eq!(
+                    unsafe { window.find_delimiters64_avx512(Delimiters::default()) },
+                    expected
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_find_delimiters() {
-        let lines = vec![
+        let lines = [
             "Bāgepalli;17.8",
             "San Fernando;-1.9",
             "Kika;4.3",
@@ -214,7 +559,7 @@ mod tests {
         let str = lines.join("\n");
         let bytes = str.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[..32], b'\n'));
 
@@ -227,7 +572,8 @@ mod tests {
         );
 
         let start = next(newlines);
-        let (semicolons, newlines) = bytes[start..start + 32].find_delimiters();
+        let (semicolons, newlines) =
+            bytes[start..start + 32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[start..start + 32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[start..start + 32], b'\n'));
 
@@ -240,7 +586,8 @@ mod tests {
         );
 
         let start = start + next(newlines);
-        let (semicolons, newlines) = bytes[start..start + 32].find_delimiters();
+        let (semicolons, newlines) =
+            bytes[start..start + 32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[start..start + 32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[start..start + 32], b'\n'));
 
@@ -254,7 +601,7 @@ mod tests {
 
         let start = start + next(newlines);
         let end = start + 32.min(bytes.len() - start);
-        let (semicolons, newlines) = bytes[start..end].find_delimiters();
+        let (semicolons, newlines) = bytes[start..end].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[start..end], b';'));
         assert_eq!(newlines, expected_mask(&bytes[start..end], b'\n'));
 
@@ -268,7 +615,7 @@ mod tests {
         let line = "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysiliogogogoch;12.3\n";
         let bytes = line.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, 0);
         assert_eq!(newlines, 0);
     }
@@ -278,7 +625,7 @@ mod tests {
         let lines = "Melbourne;23.4\nSan Francisco;-1.2\n";
         let bytes = lines.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[..32], b'\n'));
         assert_eq!(semicolons.count_ones(), 2); // Two semicolons visible
@@ -290,7 +637,7 @@ mod tests {
         let lines = "Xi;1.2\nBo;3.4\nAb;5.6\nCd;7.8\nLeftover";
         let bytes = lines.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[..32], b'\n'));
         assert_eq!(semicolons.count_ones(), 4);
@@ -301,7 +648,7 @@ mod tests {
     fn test_delimiter_at_position_zero() {
         let bytes = b";12.3\nAbc;4.5\nDef;6.7\nGhi;8.9\nJon;3.3\n";
 
-        let (semicolons, _) = bytes[..32].find_delimiters();
+        let (semicolons, _) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert!(semicolons & 1 != 0, "Expected semicolon at position 0");
     }
@@ -312,7 +659,7 @@ mod tests {
         assert_eq!(line.len(), 32);
         let bytes = line.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[..32], b'\n'));
         assert!(newlines & (1 << 31) != 0, "Expected newline at position 31");
@@ -323,7 +670,7 @@ mod tests {
         let line = "1234567;8\n901234;6.7\nAbcdef;8.9\n";
         let bytes = line.as_bytes();
 
-        let (semicolons, newlines) = bytes[..32].find_delimiters();
+        let (semicolons, newlines) = bytes[..32].find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(&bytes[..32], b';'));
         assert_eq!(newlines, expected_mask(&bytes[..32], b'\n'));
 
@@ -340,7 +687,7 @@ mod tests {
         let bytes = line.as_bytes();
         assert!(bytes.len() < 32);
 
-        let (semicolons, newlines) = bytes.find_delimiters();
+        let (semicolons, newlines) = bytes.find_delimiters(Delimiters::default());
         assert_eq!(semicolons, expected_mask(bytes, b';'));
         assert_eq!(newlines, expected_mask(bytes, b'\n'));
     }
@@ -371,7 +718,7 @@ mod tests {
 
     #[test]
     fn test_byte_position_realistic() {
-        let lines = vec![
+        let lines = [
             "Bāgepalli;17.8",
             "San Fernando;-1.9",
             "Kika;4.3",
@@ -0,0 +1,2786 @@
+use crate::byte_buffer::{ByteBuffer, Delimiters};
+use crate::error::{ConfigError, Error, RowError};
+use crate::hash_table::{Entry, Histogram, Table, Temperature, Tracking};
+use std::borrow::Cow;
+use std::fmt;
+
+pub mod snapshot;
+
+const DOT_BITS: u64 = 0x10101000;
+const MAGIC_MULTIPLIER: u64 = 100 * 0x1000000 + 10 * 0x10000 + 1;
+
+const TABLE_SIZE: usize = 1 << 16;
+
+/// Readable bytes required past the end of a chunk, for `parse_temp_unchecked`'s 8-byte load.
+const TAIL_PADDING: usize = 8;
+
+pub struct Results {
+    table: Tables,
+    precision: Precision,
+    /// Separator and number of fields in each composite key.
+    key_fields: (u8, usize),
+    skipped: u64,
+    quarantined: Vec<Rejected>,
+}
+
+/// The merged table, in the narrowest temperature type that holds the configured precision.
+enum Tables {
+    Narrow(Table<i16>),
+    Wide(Table<i32>),
+}
+
+/// A station's value columns, in the table's temperature type: the first column's entry, then
+/// the rest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Columns<'a> {
+    Narrow(&'a Entry<i16>, &'a [Entry<i16>]),
+    Wide(&'a Entry<i32>, &'a [Entry<i32>]),
+}
+
+impl<'a> Columns<'a> {
+    fn len(self) -> usize {
+        match self {
+            Columns::Narrow(_, rest) => 1 + rest.len(),
+            Columns::Wide(_, rest) => 1 + rest.len(),
+        }
+    }
+
+    /// The `i`th column's entry, in units of `Results::precision`. Panics if out of range.
+    pub fn column(self, i: usize) -> Entry<i32> {
+        match (self, i) {
+            (Columns::Narrow(first, _), 0) => first.widen(),
+            (Columns::Narrow(_, rest), i) => rest[i - 1].widen(),
+            (Columns::Wide(first, _), 0) => first.clone(),
+            (Columns::Wide(_, rest), i) => rest[i - 1].clone(),
+        }
+    }
+
+    /// Each column's entry, in column order.
+    pub fn iter(self) -> impl Iterator<Item = Entry<i32>> + 'a {
+        (0..self.len()).map(move |i| self.column(i))
+    }
+}
+
+impl Results {
+    /// Aggregated stations, sorted by name. Temperatures are in units of `precision()`.
+    pub fn entries(&self) -> Vec<(&[u8], Entry<i32>)> {
+        let mut entries: Vec<_> = match &self.table {
+            Tables::Narrow(table) => table
+                .entries()
+                .into_iter()
+                .map(|(name, entry)| (name, entry.widen()))
+                .collect(),
+            Tables::Wide(table) => table
+                .entries()
+                .into_iter()
+                .map(|(name, entry)| (name, entry.clone()))
+                .collect(),
+        };
+
+        entries.sort_unstable_by_key(|&(name, _)| name);
+        entries
+    }
+
+    /// Like `entries`, with each value column of `Config::schema`, borrowed from the table.
+    pub fn entries_columns(&self) -> Vec<(&[u8], Columns<'_>)> {
+        let mut entries: Vec<_> = match &self.table {
+            Tables::Narrow(table) => table
+                .entries_columns()
+                .into_iter()
+                .map(|(name, first, rest)| (name, Columns::Narrow(first, rest)))
+                .collect(),
+            Tables::Wide(table) => table
+                .entries_columns()
+                .into_iter()
+                .map(|(name, first, rest)| (name, Columns::Wide(first, rest)))
+                .collect(),
+        };
+
+        entries.sort_unstable_by_key(|&(name, _)| name);
+        entries
+    }
+
+    /// Each station's histograms, one per value column, sorted by name. Empty unless
+    /// `Config::tracking` has histograms.
+    pub fn histograms(&self) -> Vec<(&[u8], &[Histogram])> {
+        let mut histograms = match &self.table {
+            Tables::Narrow(table) => table.entries_histograms(),
+            Tables::Wide(table) => table.entries_histograms(),
+        };
+
+        histograms.retain(|(_, histograms)| !histograms.is_empty());
+        histograms.sort_unstable_by_key(|&(name, _)| name);
+        histograms
+    }
+
+    /// Each station's sums of squares, one per value column, sorted by name, for
+    /// `Entry::variance`. Empty unless `Config::tracking` has squares.
+    pub fn squares(&self) -> Vec<(&[u8], &[u128])> {
+        let mut squares = match &self.table {
+            Tables::Narrow(table) => table.entries_squares(),
+            Tables::Wide(table) => table.entries_squares(),
+        };
+
+        squares.retain(|(_, squares)| !squares.is_empty());
+        squares.sort_unstable_by_key(|&(name, _)| name);
+        squares
+    }
+
+    /// The fields of a station's composite key, in column order. Just the name for single keys.
+    pub fn key_fields<'a>(&self, name: &'a [u8]) -> impl Iterator<Item = &'a [u8]> + use<'a> {
+        let (separator, fields) = self.key_fields;
+        name.splitn(fields, move |&b| b == separator)
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Value columns per station.
+    pub fn columns(&self) -> usize {
+        match &self.table {
+            Tables::Narrow(table) => table.columns(),
+            Tables::Wide(table) => table.columns(),
+        }
+    }
+
+    /// Fields in each station's key: more than one for composite keys.
+    pub fn key_columns(&self) -> usize {
+        self.key_fields.1
+    }
+
+    pub fn tracking(&self) -> Tracking {
+        match &self.table {
+            Tables::Narrow(table) => table.tracking(),
+            Tables::Wide(table) => table.tracking(),
+        }
+    }
+
+    /// Number of malformed rows left out under `Policy::Skip` or `Policy::Quarantine`.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Malformed rows left out under `Policy::Quarantine`, in input order.
+    pub fn quarantined(&self) -> &[Rejected] {
+        &self.quarantined
+    }
+
+    /// Adds results aggregated separately, such as from another file or a snapshot, as if their
+    /// inputs had been aggregated together. Their precision, keys, columns and tracking must
+    /// match. Quarantined rows are appended, with offsets into their own input.
+    pub fn merge(&mut self, other: Results) -> Result<(), Error> {
+        if self.precision != other.precision
+            || self.key_fields != other.key_fields
+            || self.columns() != other.columns()
+            || self.tracking() != other.tracking()
+        {
+            return Err(Error::Incompatible);
+        }
+
+        match (&mut self.table, &other.table) {
+            (Tables::Narrow(ours), Tables::Narrow(theirs)) => ours.merge(theirs),
+            (Tables::Wide(ours), Tables::Wide(theirs)) => ours.merge(theirs),
+            _ => unreachable!("the same precision is aggregated in the same type"),
+        }
+
+        self.skipped += other.skipped;
+        self.quarantined.extend(other.quarantined);
+        Ok(())
+    }
+}
+
+/// A malformed row left out under `Policy::Quarantine`.
+#[derive(Debug)]
+pub struct Rejected {
+    /// What is wrong with the row, with its byte offset and line number.
+    pub error: Error,
+    /// The row, excluding its newline.
+    pub row: Vec<u8>,
+}
+
+/// What to do with malformed rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Fail the aggregation with the first malformed row.
+    #[default]
+    Strict,
+    /// Leave malformed rows out, and count them.
+    Skip,
+    /// Like `Skip`, also keeping a copy of each malformed row in `Results::quarantined`.
+    Quarantine,
+}
+
+/// How temperatures are written: `-?\d{1,int_digits}\.\d{decimals}`.
+///
+/// Temperatures are aggregated as integers, in units of `10^-decimals` degrees. Up to 4 digits in
+/// all fit an `i16`, and up to 9 an `i32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Precision {
+    /// Digits after the decimal point.
+    pub decimals: u32,
+    /// Most digits before the decimal point.
+    pub int_digits: u32,
+}
+
+impl Precision {
+    /// The 1BRC format, `-99.9` to `99.9`.
+    pub const TENTHS: Precision = Precision {
+        decimals: 1,
+        int_digits: 2,
+    };
+
+    pub fn is_supported(self) -> bool {
+        self.decimals >= 1 && self.int_digits >= 1 && self.decimals + self.int_digits <= 9
+    }
+
+    fn fits_i16(self) -> bool {
+        self.decimals + self.int_digits <= 4
+    }
+
+    /// Parses a temperature into units of `10^-decimals` degrees, or `None` if it isn't in this
+    /// format.
+    pub fn parse(self, bytes: &[u8]) -> Option<i32> {
+        let (negative, rest) = match bytes {
+            [b'-', rest @ ..] => (true, rest),
+            _ => (false, bytes),
+        };
+
+        let int_len = rest.len().checked_sub(self.decimals as usize + 1)?;
+        let (int, frac) = (&rest[..int_len], &rest[int_len + 1..]);
+
+        if !(1..=self.int_digits as usize).contains(&int.len())
+            || rest[int_len] != b'.'
+            || !int.iter().chain(frac).all(u8::is_ascii_digit)
+        {
+            return None;
+        }
+
+        let abs = int
+            .iter()
+            .chain(frac)
+            .fold(0, |acc, &d| acc * 10 + (d - b'0') as i32);
+        Some(if negative { -abs } else { abs })
+    }
+
+    /// Displays a value in units of `10^-decimals` degrees as degrees, with exactly `decimals`
+    /// places. Formatted with integer arithmetic, so it never rounds.
+    pub fn degrees(self, units: i64) -> Degrees {
+        Degrees {
+            units,
+            decimals: self.decimals,
+        }
+    }
+
+    /// Converts a value in units of `10^-decimals` degrees squared, such as a variance, to
+    /// degrees squared. Not rounded.
+    pub fn to_degrees_squared(self, value: f64) -> f64 {
+        value / 10f64.powi(2 * self.decimals as i32)
+    }
+}
+
+/// A value in degrees, from `Precision::degrees`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Degrees {
+    units: i64,
+    decimals: u32,
+}
+
+impl fmt::Display for Degrees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = 10u64.pow(self.decimals);
+        let abs = self.units.unsigned_abs();
+        let sign = if self.units < 0 { "-" } else { "" };
+        let decimals = self.decimals as usize;
+        write!(f, "{sign}{}.{:0decimals$}", abs / scale, abs % scale)
+    }
+}
+
+/// Which columns of each row hold the station name and its temperatures. Rows must have at least
+/// `columns` columns: the last one takes the rest of the row, separators included, as the
+/// temperature does in the 1BRC format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Schema {
+    pub columns: usize,
+    /// Bit `i` is set if the 0-based column `i` is part of the station's key. Several make a
+    /// composite key, such as `(station, month)`: their fields joined by the separator, in column
+    /// order. `Results::key_fields` splits them back out.
+    pub keys: u64,
+    /// Bit `i` is set if the 0-based column `i` holds a temperature. Each is aggregated
+    /// separately, with the same `Precision`, and reported in column order.
+    pub values: u64,
+}
+
+impl Schema {
+    /// The 1BRC format, `name;temp`.
+    pub const NAME_TEMP: Schema = Schema {
+        columns: 2,
+        keys: 1 << 0,
+        values: 1 << 1,
+    };
+
+    /// Most key columns per row.
+    pub const MAX_KEYS: usize = 4;
+
+    /// Most value columns per row.
+    pub const MAX_VALUES: usize = 8;
+
+    pub fn is_supported(self) -> bool {
+        let past_end = (self.keys | self.values)
+            .checked_shr(self.columns as u32)
+            .unwrap_or(0);
+
+        past_end == 0
+            && self.keys & self.values == 0
+            && (1..=Schema::MAX_KEYS).contains(&self.key_columns())
+            && (1..=Schema::MAX_VALUES).contains(&self.value_columns())
+    }
+
+    pub fn key_columns(self) -> usize {
+        self.keys.count_ones() as usize
+    }
+
+    pub fn value_columns(self) -> usize {
+        self.values.count_ones() as usize
+    }
+
+    /// Whether the key columns are next to each other, so their joined fields are already a
+    /// slice of the row.
+    fn keys_adjacent(self) -> bool {
+        let run = self.keys >> self.keys.trailing_zeros();
+        run & run.wrapping_add(1) == 0
+    }
+
+    /// The last column needed, past which the rest of the row is ignored.
+    fn last_needed(self) -> usize {
+        63 - (self.keys | self.values).leading_zeros() as usize
+    }
+}
+
+/// Instruction set used for delimiter scanning and table lookups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Avx512,
+    Avx2,
+    Sse2,
+}
+
+impl Backend {
+    /// The fastest backend the running CPU supports.
+    pub fn detect() -> Self {
+        if Backend::Avx512.is_supported() {
+            Backend::Avx512
+        } else if Backend::Avx2.is_supported() {
+            Backend::Avx2
+        } else {
+            Backend::Sse2
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Avx512 => {
+                is_x86_feature_detected!("avx512f")
+                    && is_x86_feature_detected!("avx512bw")
+                    && is_x86_feature_detected!("avx2")
+            }
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+        }
+    }
+}
+
+pub struct Config {
+    pub threads: usize,
+    pub backend: Backend,
+    /// Longest accepted station name, in bytes. Names are stored with a 32-bit length.
+    pub max_name_len: usize,
+    pub policy: Policy,
+    pub precision: Precision,
+    pub delimiters: Delimiters,
+    pub schema: Schema,
+    /// Stats to keep beyond min/mean/max, for `Results::histograms` and `Results::squares`. Rows
+    /// are then read one at a time, and histograms only support precisions of up to 4 digits.
+    pub tracking: Tracking,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            backend: Backend::detect(),
+            max_name_len: u32::MAX as usize,
+            policy: Policy::Strict,
+            precision: Precision::TENTHS,
+            delimiters: Delimiters::default(),
+            schema: Schema::NAME_TEMP,
+            tracking: Tracking::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Whether the engine can aggregate with this config on the running CPU.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        if !self.backend.is_supported() {
+            return Err(ConfigError::UnsupportedBackend(self.backend));
+        }
+        if !self.precision.is_supported() {
+            return Err(ConfigError::UnsupportedPrecision(self.precision));
+        }
+        if !self.delimiters.is_supported() {
+            return Err(ConfigError::UnsupportedDelimiters(self.delimiters));
+        }
+        if !self.schema.is_supported() {
+            return Err(ConfigError::UnsupportedSchema);
+        }
+        if self.tracking.histograms && !self.precision.fits_i16() {
+            return Err(ConfigError::HistogramsTooPrecise(self.precision));
+        }
+
+        Ok(())
+    }
+}
+
+/// Delimiter scanning and table lookup for one backend. The engine is generic over it so each
+/// backend gets its own copy of the hot loop, compiled with its target features enabled.
+trait Isa {
+    fn find_delimiters64(buf: &[u8], delims: Delimiters) -> (u64, u64);
+
+    fn lookup_exact<T: Temperature>(tbl: &Table<T>, hash: u64, prefix: u64, name: &[u8]) -> usize;
+}
+
+struct Avx512;
+struct Avx2;
+struct Sse2;
+
+// Only ever instantiated from `aggregate_chunk_avx512`, after `Backend::is_supported`.
+impl Isa for Avx512 {
+    #[inline(always)]
+    fn find_delimiters64(buf: &[u8], delims: Delimiters) -> (u64, u64) {
+        unsafe { buf.find_delimiters64_avx512(delims) }
+    }
+
+    #[inline(always)]
+    fn lookup_exact<T: Temperature>(tbl: &Table<T>, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        unsafe { tbl.lookup_exact_avx512(hash, prefix, name) }
+    }
+}
+
+// Only ever instantiated from `aggregate_chunk_avx2`, after `Backend::is_supported`.
+impl Isa for Avx2 {
+    #[inline(always)]
+    fn find_delimiters64(buf: &[u8], delims: Delimiters) -> (u64, u64) {
+        unsafe { buf.find_delimiters64_avx2(delims) }
+    }
+
+    #[inline(always)]
+    fn lookup_exact<T: Temperature>(tbl: &Table<T>, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        tbl.lookup_exact_scalar(hash, prefix, name)
+    }
+}
+
+impl Isa for Sse2 {
+    #[inline(always)]
+    fn find_delimiters64(buf: &[u8], delims: Delimiters) -> (u64, u64) {
+        buf.find_delimiters64_sse2(delims)
+    }
+
+    #[inline(always)]
+    fn lookup_exact<T: Temperature>(tbl: &Table<T>, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        tbl.lookup_exact_scalar(hash, prefix, name)
+    }
+}
+
+/// How the hot loop reads temperatures. Generic like `Isa`, so the 1BRC format keeps its
+/// branch-free parser.
+trait Temps {
+    /// Shortest and longest temperature, in bytes.
+    fn len_range(precision: Precision) -> (usize, usize);
+
+    /// Parses a temperature, and whether it was well-formed. The value is meaningless if not, so
+    /// the hot loop can check three rows with one branch.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must be within `len_range`, or one byte longer, and 8 bytes must be readable from
+    /// its start.
+    unsafe fn parse(bytes: &[u8], precision: Precision) -> (i32, bool);
+}
+
+/// `Precision::TENTHS`.
+struct Tenths;
+
+/// Any other precision.
+struct Scaled;
+
+impl Temps for Tenths {
+    #[inline(always)]
+    fn len_range(_: Precision) -> (usize, usize) {
+        (3, 5)
+    }
+
+    #[inline(always)]
+    unsafe fn parse(bytes: &[u8], _: Precision) -> (i32, bool) {
+        let word = unsafe { load_temp(bytes) };
+        (
+            parse_temp_word(word) as i32,
+            is_temp_format(word, bytes.len()),
+        )
+    }
+}
+
+impl Temps for Scaled {
+    #[inline(always)]
+    fn len_range(precision: Precision) -> (usize, usize) {
+        let min = precision.decimals as usize + 2;
+        (min, min + precision.int_digits as usize)
+    }
+
+    #[inline(always)]
+    unsafe fn parse(bytes: &[u8], precision: Precision) -> (i32, bool) {
+        match precision.parse(bytes) {
+            Some(temp) => (temp, true),
+            None => (0, false),
+        }
+    }
+}
+
+/// How the hot loop reads rows. Generic like `Isa`, so the 1BRC layout keeps its interleaved loop
+/// without checking the schema or tracking on every row.
+trait Rows {
+    /// Whether rows are `name;temp`, with nothing tracked beyond `Entry`. Other rows are read one
+    /// at a time by `process_columns`.
+    const PLAIN: bool;
+}
+
+/// `Schema::NAME_TEMP`, without tracking.
+struct Plain;
+
+/// Any other schema, or with tracking.
+struct General;
+
+impl Rows for Plain {
+    const PLAIN: bool = true;
+}
+
+impl Rows for General {
+    const PLAIN: bool = false;
+}
+
+/// Aggregates min/sum/count/max per station over `name;temp\n` rows.
+pub fn aggregate(buf: &[u8]) -> Result<Results, Error> {
+    aggregate_with(buf, &Config::default())
+}
+
+/// Splits `buf` into `config.threads` chunks, aggregates each into its own table on its own
+/// thread, then merges the tables.
+///
+/// Fails with `Error::Config` if `config` isn't supported. Under `Policy::Strict`, also fails on
+/// the first malformed row in `buf`.
+pub fn aggregate_with(buf: &[u8], config: &Config) -> Result<Results, Error> {
+    config.check().map_err(Error::Config)?;
+
+    if config.precision.fits_i16() {
+        aggregate_as::<i16>(buf, config, Tables::Narrow)
+    } else {
+        aggregate_as::<i32>(buf, config, Tables::Wide)
+    }
+}
+
+fn aggregate_as<T: Temperature>(
+    buf: &[u8],
+    config: &Config,
+    wrap: fn(Table<T>) -> Tables,
+) -> Result<Results, Error> {
+    let backend = config.backend;
+    let delims = config.delimiters;
+
+    // The final row may or may not end in a newline.
+    let effective_buf = buf.strip_suffix(&[delims.terminator]).unwrap_or(buf);
+
+    // Rows in the body end at least `TAIL_PADDING` bytes before the end of `buf`, so they can
+    // be parsed in place. The few rows after that are parsed from a padded copy.
+    let limit = buf
+        .len()
+        .saturating_sub(TAIL_PADDING)
+        .min(effective_buf.len());
+    let (body, tail) = split_at_newline(effective_buf, limit, delims.terminator);
+
+    let chunks = split_regions(body, config.threads.max(1), delims.terminator);
+
+    let tables: Vec<Result<(Table<T>, Checks), RowError>> = std::thread::scope(|s| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .map(|&chunk| {
+                s.spawn(move || {
+                    let columns = config.schema.value_columns();
+                    let mut tbl = Table::with_tracking(TABLE_SIZE, columns, config.tracking);
+                    let mut checks = Checks::new(config);
+                    unsafe { dispatch(backend, &mut tbl, chunk, &mut checks) }?;
+                    Ok((tbl, checks))
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    // Chunks stop at the first malformed row they see, which may not be the first in `buf`.
+    let malformed = || first_malformed_row(effective_buf, &Checks::new(config));
+
+    let mut tables = tables
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| malformed())?;
+
+    let (mut table, mut checks) = tables.swap_remove(0);
+    for (other, other_checks) in tables {
+        table.merge(&other);
+        checks.absorb(other_checks);
+    }
+
+    let mut padded = tail.to_vec();
+    padded.resize(tail.len() + TAIL_PADDING, 0);
+    let mut tail_checks = Checks::new(config);
+    unsafe { dispatch(backend, &mut table, &padded[..tail.len()], &mut tail_checks) }
+        .map_err(|_| malformed())?;
+    tail_checks.relocate(padded.as_ptr(), tail.as_ptr());
+    checks.absorb(tail_checks);
+
+    // Names are only validated once per station.
+    let is_invalid = |name: &[u8]| str::from_utf8(name).is_err();
+    if table.entries().iter().any(|&(name, _)| is_invalid(name)) {
+        if config.policy == Policy::Strict {
+            return Err(malformed());
+        }
+
+        reject_invalid_names(effective_buf, &mut table, &mut checks);
+    }
+
+    let quarantined = match config.policy {
+        Policy::Quarantine => quarantine(buf, checks.rejected, delims.terminator),
+        _ => Vec::new(),
+    };
+
+    Ok(Results {
+        table: wrap(table),
+        precision: config.precision,
+        key_fields: (delims.separator, config.schema.key_columns()),
+        skipped: checks.skipped,
+        quarantined,
+    })
+}
+
+/// Per-chunk row rules, and the malformed rows left out so far.
+struct Checks {
+    max_name_len: usize,
+    precision: Precision,
+    delims: Delimiters,
+    schema: Schema,
+    tracking: Tracking,
+    policy: Policy,
+    /// Scratch space for composite keys whose fields aren't adjacent.
+    key: Vec<u8>,
+    skipped: u64,
+    /// Kind, start address and length of each quarantined row.
+    rejected: Vec<(RowError, usize, usize)>,
+}
+
+impl Checks {
+    fn new(config: &Config) -> Self {
+        Self {
+            max_name_len: config.max_name_len,
+            precision: config.precision,
+            delims: config.delimiters,
+            schema: config.schema,
+            tracking: config.tracking,
+            policy: config.policy,
+            key: Vec::new(),
+            skipped: 0,
+            rejected: Vec::new(),
+        }
+    }
+
+    /// Fails with `kind` under `Policy::Strict`. Otherwise records `row` and carries on.
+    #[cold]
+    #[inline(never)]
+    fn reject(&mut self, kind: RowError, row: &[u8]) -> Result<(), RowError> {
+        match self.policy {
+            Policy::Strict => return Err(kind),
+            Policy::Skip => {}
+            Policy::Quarantine => self.rejected.push((kind, row.as_ptr() as usize, row.len())),
+        }
+
+        self.skipped += 1;
+        Ok(())
+    }
+
+    /// Moves rows rejected from a copy of the input starting at `copy` to the same place in
+    /// the original, starting at `original`.
+    fn relocate(&mut self, copy: *const u8, original: *const u8) {
+        for (_, start, _) in &mut self.rejected {
+            *start = *start - copy as usize + original as usize;
+        }
+    }
+
+    /// Takes over the rows rejected by another chunk.
+    fn absorb(&mut self, mut other: Checks) {
+        self.skipped += other.skipped;
+        self.rejected.append(&mut other.rejected);
+    }
+}
+
+/// Drops stations whose names aren't valid UTF-8, rejecting each of their rows. Slow, so only
+/// used once such a name has been found.
+#[cold]
+fn reject_invalid_names<T: Temperature>(buf: &[u8], table: &mut Table<T>, checks: &mut Checks) {
+    table.retain(|name, _| str::from_utf8(name).is_ok());
+
+    let terminator = checks.delims.terminator;
+    for row in buf.split(|&b| b == terminator) {
+        if check_row(row, checks) == Err(RowError::InvalidUtf8) {
+            let _ = checks.reject(RowError::InvalidUtf8, row);
+        }
+    }
+}
+
+/// Copies out rejected rows, as (kind, start address, length) within `buf`, in input order.
+#[cold]
+fn quarantine(
+    buf: &[u8],
+    mut rows: Vec<(RowError, usize, usize)>,
+    terminator: u8,
+) -> Vec<Rejected> {
+    rows.sort_unstable_by_key(|&(_, start, _)| start);
+
+    let mut line = 1;
+    let mut counted = 0;
+
+    rows.into_iter()
+        .map(|(kind, start, len)| {
+            let offset = start - buf.as_ptr() as usize;
+            line += buf[counted..offset]
+                .iter()
+                .filter(|&&b| b == terminator)
+                .count();
+            counted = offset;
+
+            Rejected {
+                error: Error::row(kind, offset, line),
+                row: buf[offset..offset + len].to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Scans `buf` row by row for the first malformed one. Slow, so only used once aggregation has
+/// failed.
+#[cold]
+fn first_malformed_row(buf: &[u8], checks: &Checks) -> Error {
+    let mut offset = 0;
+
+    for (i, row) in buf.split(|&b| b == checks.delims.terminator).enumerate() {
+        if let Err(kind) = check_row(row, checks) {
+            return Error::row(kind, offset, i + 1);
+        }
+
+        offset += row.len() + 1;
+    }
+
+    unreachable!("aggregation failed on a well-formed input");
+}
+
+/// Validates a row, excluding its newline, by the same rules the engine applies.
+fn check_row(row: &[u8], checks: &Checks) -> Result<(), RowError> {
+    let (name, _) = check_row_shape(row, checks)?;
+
+    if str::from_utf8(&name).is_err() {
+        return Err(RowError::InvalidUtf8);
+    }
+
+    Ok(())
+}
+
+/// Like `check_row`, without the UTF-8 check. Returns the name and parsed temperatures, one per
+/// value column.
+#[inline(always)]
+fn check_row_shape<'a>(
+    row: &'a [u8],
+    checks: &Checks,
+) -> Result<(Cow<'a, [u8]>, [i32; Schema::MAX_VALUES]), RowError> {
+    let row = match row {
+        [row @ .., b'\r'] if checks.delims.crlf => row,
+        _ => row,
+    };
+
+    let schema = checks.schema;
+    let separator = checks.delims.separator;
+    let mut keys = [&row[..0]; Schema::MAX_KEYS];
+    let mut temps = [&row[..0]; Schema::MAX_VALUES];
+    let (mut columns, mut key_fields, mut values) = (0, 0, 0);
+
+    for column in row.splitn(schema.columns, |&b| b == separator) {
+        if schema.keys >> columns & 1 == 1 {
+            keys[key_fields] = column;
+            key_fields += 1;
+        } else if schema.values >> columns & 1 == 1 {
+            temps[values] = column;
+            values += 1;
+        }
+        columns += 1;
+    }
+
+    if columns < schema.columns {
+        return Err(RowError::MissingSeparator);
+    }
+
+    let name = if schema.keys_adjacent() {
+        let (first, last) = (keys[0], keys[key_fields - 1]);
+        let start = first.as_ptr() as usize - row.as_ptr() as usize;
+        let end = last.as_ptr() as usize - row.as_ptr() as usize + last.len();
+        Cow::Borrowed(&row[start..end])
+    } else {
+        let mut key = Vec::new();
+        Table::composite_key(keys[..key_fields].iter().copied(), separator, &mut key);
+        Cow::Owned(key)
+    };
+
+    if name.is_empty() {
+        return Err(RowError::EmptyName);
+    }
+
+    if name.len() > checks.max_name_len {
+        return Err(RowError::NameTooLong);
+    }
+
+    let mut parsed = [0; Schema::MAX_VALUES];
+    for (parsed, temp) in parsed.iter_mut().zip(&temps[..values]) {
+        *parsed = checks
+            .precision
+            .parse(temp)
+            .ok_or(RowError::BadTemperature)?;
+    }
+
+    Ok((name, parsed))
+}
+
+/// # Safety
+///
+/// `backend` must be supported, and `TAIL_PADDING` bytes must be readable past the end of `buf`.
+unsafe fn dispatch<T: Temperature>(
+    backend: Backend,
+    tbl: &mut Table<T>,
+    buf: &[u8],
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    let plain = checks.schema == Schema::NAME_TEMP && checks.tracking == Tracking::default();
+    let tenths = checks.precision == Precision::TENTHS;
+
+    unsafe {
+        match (plain, tenths) {
+            (true, true) => dispatch_backend::<Plain, Tenths, T>(backend, tbl, buf, checks),
+            (true, false) => dispatch_backend::<Plain, Scaled, T>(backend, tbl, buf, checks),
+            (false, true) => dispatch_backend::<General, Tenths, T>(backend, tbl, buf, checks),
+            (false, false) => dispatch_backend::<General, Scaled, T>(backend, tbl, buf, checks),
+        }
+    }
+}
+
+/// # Safety
+///
+/// As for `dispatch`.
+unsafe fn dispatch_backend<R: Rows, P: Temps, T: Temperature>(
+    backend: Backend,
+    tbl: &mut Table<T>,
+    buf: &[u8],
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    unsafe {
+        match backend {
+            Backend::Avx512 => aggregate_chunk_avx512::<R, P, T>(tbl, buf, checks),
+            Backend::Avx2 => aggregate_chunk_avx2::<R, P, T>(tbl, buf, checks),
+            Backend::Sse2 => aggregate_chunk::<Sse2, R, P, T>(tbl, buf, checks),
+        }
+    }
+}
+
+#[target_feature(enable = "avx512f,avx512bw,avx2")]
+unsafe fn aggregate_chunk_avx512<R: Rows, P: Temps, T: Temperature>(
+    tbl: &mut Table<T>,
+    buf: &[u8],
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    unsafe { aggregate_chunk::<Avx512, R, P, T>(tbl, buf, checks) }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn aggregate_chunk_avx2<R: Rows, P: Temps, T: Temperature>(
+    tbl: &mut Table<T>,
+    buf: &[u8],
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    unsafe { aggregate_chunk::<Avx2, R, P, T>(tbl, buf, checks) }
+}
+
+/// Under `Policy::Strict`, stops at the first malformed row it sees. Names are not checked for
+/// valid UTF-8.
+///
+/// # Safety
+///
+/// `TAIL_PADDING` bytes must be readable past the end of `buf`. The same holds for the regions
+/// and lines handed to the helpers below.
+#[inline(always)]
+unsafe fn aggregate_chunk<I: Isa, R: Rows, P: Temps, T: Temperature>(
+    tbl: &mut Table<T>,
+    buf: &[u8],
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    let max_name_len = checks.max_name_len;
+    let precision = checks.precision;
+    let delims = checks.delims;
+    let temp_len = temp_len_range::<P>(precision, delims);
+    let regions = split_regions(buf, 3, delims.terminator);
+
+    // Other schemas, and tracking, skip straight to `cleanup_region`, which reads one row at a
+    // time.
+    let interleave = R::PLAIN;
+    let (region_a, region_b, region_c) = (regions[0], regions[1], regions[2]);
+
+    let mut cursor_a = 0;
+    let mut cursor_b = 0;
+    let mut cursor_c = 0;
+
+    while interleave
+        && cursor_a < region_a.len()
+        && cursor_b < region_b.len()
+        && cursor_c < region_c.len()
+    {
+        let end_a = cursor_a + 64.min(region_a.len() - cursor_a);
+        let end_b = cursor_b + 64.min(region_b.len() - cursor_b);
+        let end_c = cursor_c + 64.min(region_c.len() - cursor_c);
+
+        let window_a = &region_a[cursor_a..end_a];
+        let window_b = &region_b[cursor_b..end_b];
+        let window_c = &region_c[cursor_c..end_c];
+
+        let (mut semi_a, mut nl_a) = I::find_delimiters64(window_a, delims);
+        let (mut semi_b, mut nl_b) = I::find_delimiters64(window_b, delims);
+        let (mut semi_c, mut nl_c) = I::find_delimiters64(window_c, delims);
+
+        if nl_a == 0 {
+            cursor_a = unsafe {
+                process_long_line::<I, P, T>(region_a, tbl, cursor_a, region_a.len(), checks)?
+            };
+            continue;
+        }
+
+        if nl_b == 0 {
+            cursor_b = unsafe {
+                process_long_line::<I, P, T>(region_b, tbl, cursor_b, region_b.len(), checks)?
+            };
+            continue;
+        }
+
+        if nl_c == 0 {
+            cursor_c = unsafe {
+                process_long_line::<I, P, T>(region_c, tbl, cursor_c, region_c.len(), checks)?
+            };
+            continue;
+        }
+
+        let mut line_cursor_a = 0;
+        let mut line_cursor_b = 0;
+        let mut line_cursor_c = 0;
+
+        while nl_a != 0 && nl_b != 0 && nl_c != 0 {
+            let semi_pos_a = semi_a.trailing_zeros() as usize;
+            let semi_pos_b = semi_b.trailing_zeros() as usize;
+            let semi_pos_c = semi_c.trailing_zeros() as usize;
+            let nl_pos_a = nl_a.trailing_zeros() as usize;
+            let nl_pos_b = nl_b.trailing_zeros() as usize;
+            let nl_pos_c = nl_c.trailing_zeros() as usize;
+
+            // Malformed rows are left to `process_line` to report.
+            let malformed =
+                is_malformed(line_cursor_a, semi_pos_a, nl_pos_a, max_name_len, temp_len)
+                    | is_malformed(line_cursor_b, semi_pos_b, nl_pos_b, max_name_len, temp_len)
+                    | is_malformed(line_cursor_c, semi_pos_c, nl_pos_c, max_name_len, temp_len);
+
+            if malformed {
+                break;
+            }
+
+            let name_a = unsafe { window_a.get_unchecked(line_cursor_a..semi_pos_a) };
+            let name_b = unsafe { window_b.get_unchecked(line_cursor_b..semi_pos_b) };
+            let name_c = unsafe { window_c.get_unchecked(line_cursor_c..semi_pos_c) };
+            let temp_a = unsafe { temp_slice(window_a, semi_pos_a, nl_pos_a, delims) };
+            let temp_b = unsafe { temp_slice(window_b, semi_pos_b, nl_pos_b, delims) };
+            let temp_c = unsafe { temp_slice(window_c, semi_pos_c, nl_pos_c, delims) };
+
+            let (hash_a, prefix_a) = Table::hash(name_a);
+            let (hash_b, prefix_b) = Table::hash(name_b);
+            let (hash_c, prefix_c) = Table::hash(name_c);
+
+            tbl.prefetch(hash_a);
+            tbl.prefetch(hash_b);
+            tbl.prefetch(hash_c);
+
+            let (parsed_temp_a, ok_a) = unsafe { P::parse(temp_a, precision) };
+            let (parsed_temp_b, ok_b) = unsafe { P::parse(temp_b, precision) };
+            let (parsed_temp_c, ok_c) = unsafe { P::parse(temp_c, precision) };
+
+            if !(ok_a & ok_b & ok_c) {
+                break;
+            }
+
+            let parsed_temp_a = T::from_i32(parsed_temp_a);
+            let parsed_temp_b = T::from_i32(parsed_temp_b);
+            let parsed_temp_c = T::from_i32(parsed_temp_c);
+
+            // Each update must land before the next lookup, or two new names sharing an empty
+            // slot would both be written to it.
+            let slot_a = I::lookup_exact(tbl, hash_a, prefix_a, name_a);
+            tbl.update(slot_a, hash_a, prefix_a, name_a, parsed_temp_a);
+            let slot_b = I::lookup_exact(tbl, hash_b, prefix_b, name_b);
+            tbl.update(slot_b, hash_b, prefix_b, name_b, parsed_temp_b);
+            let slot_c = I::lookup_exact(tbl, hash_c, prefix_c, name_c);
+            tbl.update(slot_c, hash_c, prefix_c, name_c, parsed_temp_c);
+
+            // Clears every separator up to the newline, so a row with extra separators can't
+            // pair the next row with one of them.
+            semi_a &= !(nl_a ^ (nl_a - 1));
+            semi_b &= !(nl_b ^ (nl_b - 1));
+            semi_c &= !(nl_c ^ (nl_c - 1));
+
+            nl_a &= nl_a - 1;
+            nl_b &= nl_b - 1;
+            nl_c &= nl_c - 1;
+
+            line_cursor_a = nl_pos_a + 1;
+            line_cursor_b = nl_pos_b + 1;
+            line_cursor_c = nl_pos_c + 1;
+        }
+
+        while nl_a != 0 {
+            line_cursor_a = unsafe {
+                process_line::<I, R, P, T>(window_a, tbl, line_cursor_a, semi_a, nl_a, checks)?
+            };
+            semi_a &= !(nl_a ^ (nl_a - 1));
+            nl_a &= nl_a - 1;
+        }
+
+        while nl_b != 0 {
+            line_cursor_b = unsafe {
+                process_line::<I, R, P, T>(window_b, tbl, line_cursor_b, semi_b, nl_b, checks)?
+            };
+            semi_b &= !(nl_b ^ (nl_b - 1));
+            nl_b &= nl_b - 1;
+        }
+
+        while nl_c != 0 {
+            line_cursor_c = unsafe {
+                process_line::<I, R, P, T>(window_c, tbl, line_cursor_c, semi_c, nl_c, checks)?
+            };
+            semi_c &= !(nl_c ^ (nl_c - 1));
+            nl_c &= nl_c - 1;
+        }
+
+        cursor_a += line_cursor_a;
+        cursor_b += line_cursor_b;
+        cursor_c += line_cursor_c;
+    }
+
+    unsafe { cleanup_region::<I, R, P, T>(tbl, region_a, cursor_a, checks)? };
+    unsafe { cleanup_region::<I, R, P, T>(tbl, region_b, cursor_b, checks)? };
+    unsafe { cleanup_region::<I, R, P, T>(tbl, region_c, cursor_c, checks)? };
+
+    Ok(())
+}
+
+/// Whether the row at `start..nl_pos` of a window, with its first `;` at `semi_pos`, lacks a
+/// separator, has an empty name or one over `max_name_len`, or a temperature outside `temp_len`.
+/// Branch-free, so the hot loop can check three rows at once. Only once this passes is it safe to
+/// call `Temps::parse`.
+#[inline(always)]
+fn is_malformed(
+    start: usize,
+    semi_pos: usize,
+    nl_pos: usize,
+    max_name_len: usize,
+    (min_temp_len, max_temp_len): (usize, usize),
+) -> bool {
+    // Without a separator in the row, `semi_pos > nl_pos` and the temperature length wraps.
+    let temp_len = nl_pos.wrapping_sub(semi_pos).wrapping_sub(1);
+    let bad_temp_len = temp_len.wrapping_sub(min_temp_len) > max_temp_len - min_temp_len;
+    let name_len = semi_pos.wrapping_sub(start);
+    let bad_name_len = (name_len == 0) | (name_len > max_name_len);
+
+    bad_temp_len | bad_name_len
+}
+
+/// `Temps::len_range`, allowing for a `\r` before the terminator.
+#[inline(always)]
+fn temp_len_range<P: Temps>(precision: Precision, delims: Delimiters) -> (usize, usize) {
+    let (min, max) = P::len_range(precision);
+    (min, max + delims.crlf as usize)
+}
+
+/// The temperature of a row with its separator at `semi_pos` and terminator at `nl_pos`,
+/// without any `\r` before the terminator.
+///
+/// # Safety
+///
+/// The row must have passed `is_malformed`.
+#[inline(always)]
+unsafe fn temp_slice(window: &[u8], semi_pos: usize, nl_pos: usize, delims: Delimiters) -> &[u8] {
+    unsafe {
+        let cr = delims.crlf && *window.get_unchecked(nl_pos - 1) == b'\r';
+        window.get_unchecked(semi_pos + 1..nl_pos - cr as usize)
+    }
+}
+
+/// Parses a `-?\d?\d\.\d` temperature into tenths of a degree.
+#[inline(always)]
+pub fn parse_temp(bytes: &[u8]) -> i16 {
+    let len = bytes.len().min(7);
+    let mut padded = [0u8; 8];
+    padded[..len].copy_from_slice(&bytes[..len]);
+
+    unsafe { parse_temp_unchecked(&padded[..len]) }
+}
+
+/// Like `parse_temp`, but `None` for anything other than a `-?\d?\d\.\d` temperature.
+///
+/// Well-formed temperatures are recognised and parsed without branching. Anything else falls
+/// back to a byte-by-byte parser, which has the final say.
+pub fn parse_temp_checked(bytes: &[u8]) -> Option<i16> {
+    let len = bytes.len().min(8);
+    let mut padded = [0u8; 8];
+    padded[..len].copy_from_slice(&bytes[..len]);
+    let n = u64::from_le_bytes(padded);
+
+    if is_temp_format(n, bytes.len()) {
+        Some(parse_temp_word(n))
+    } else {
+        parse_temp_slow(bytes)
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn parse_temp_slow(bytes: &[u8]) -> Option<i16> {
+    Precision::TENTHS.parse(bytes).map(|temp| temp as i16)
+}
+
+/// Whether `n`, holding `len` bytes with the rest zeroed, is a `-?\d?\d\.\d` temperature.
+/// Branch-free, and any `len` is allowed.
+#[inline(always)]
+fn is_temp_format(n: u64, len: usize) -> bool {
+    let sign = (n & 0xff == b'-' as u64) as usize;
+    let dot_shift = len.wrapping_sub(2).wrapping_mul(8) as u32;
+    let has_dot = n.wrapping_shr(dot_shift) & 0xff == b'.' as u64;
+
+    // Sets bit 4 of every byte that isn't an ASCII digit: either its high nibble isn't 3, or its
+    // low nibble is over 9.
+    let high = ((n & 0xf0f0f0f0f0f0f0f0) ^ 0x3030303030303030) >> 4;
+    let low = n & 0x0f0f0f0f0f0f0f0f;
+    let not_digit = ((high + 0x0f0f0f0f0f0f0f0f) | (low + 0x0606060606060606)) & 0x1010101010101010;
+
+    // Every byte after the sign must be a digit, except the dot. Wrong for `len >= 8`, but so is
+    // the length check below.
+    let bytes = 1u64
+        .wrapping_shl(len.wrapping_mul(8) as u32)
+        .wrapping_sub(1);
+    let digits = bytes & !0xffu64.wrapping_shl(dot_shift) & u64::MAX.wrapping_shl(sign as u32 * 8);
+    let body_len_ok = len.wrapping_sub(sign).wrapping_sub(3) <= 1;
+
+    (not_digit & digits == 0) & has_dot & body_len_ok
+}
+
+/// Like `parse_temp`, but loads 8 bytes in place rather than copying.
+///
+/// # Safety
+///
+/// 8 bytes must be readable from the start of `bytes`.
+#[inline(always)]
+pub unsafe fn parse_temp_unchecked(bytes: &[u8]) -> i16 {
+    parse_temp_word(unsafe { load_temp(bytes) })
+}
+
+/// Loads up to 7 bytes into a `u64`, zeroing the rest.
+///
+/// # Safety
+///
+/// 8 bytes must be readable from the start of `bytes`.
+#[inline(always)]
+unsafe fn load_temp(bytes: &[u8]) -> u64 {
+    let n = unsafe { (bytes.as_ptr() as *const u64).read_unaligned() };
+    n & ((1 << (bytes.len() * 8)) - 1)
+}
+
+/// Parses a well-formed temperature loaded by `load_temp`.
+#[inline(always)]
+fn parse_temp_word(n: u64) -> i16 {
+    let dot = (!n & DOT_BITS).trailing_zeros();
+    let sign = (((!n) << 59) as i64 >> 63) as u64;
+    let mask = !(sign & 0xff);
+    let digits = ((n & mask) << (28 - dot)) & 0xf000f0f00;
+    let abs = (digits.wrapping_mul(MAGIC_MULTIPLIER) >> 32) & 0x3FF;
+    ((abs ^ sign).wrapping_sub(sign)) as i16
+}
+
+/// Splits `buf` into `n` regions at newline boundaries, excluding the separating newlines.
+fn split_regions(buf: &[u8], n: usize, terminator: u8) -> Vec<&[u8]> {
+    let mut regions = Vec::with_capacity(n);
+    let mut rest = buf;
+
+    for i in 1..n {
+        let offset = buf.len() - rest.len();
+        let target = (buf.len() / n * i).saturating_sub(offset);
+        let (region, tail) = split_at_newline(rest, target, terminator);
+        regions.push(region);
+        rest = tail;
+    }
+
+    regions.push(rest);
+    regions
+}
+
+/// Never splits at a newline at the start of `buf`: that would turn an empty row into an empty
+/// region, which has no rows at all.
+#[inline(always)]
+fn split_at_newline(buf: &[u8], target: usize, terminator: u8) -> (&[u8], &[u8]) {
+    match buf[..target].iter().rposition(|&b| b == terminator) {
+        Some(pos) if pos > 0 => (&buf[..pos], &buf[pos + 1..]),
+        _ => (&buf[..0], buf),
+    }
+}
+
+#[inline(always)]
+unsafe fn cleanup_region<I: Isa, R: Rows, P: Temps, T: Temperature>(
+    tbl: &mut Table<T>,
+    region: &[u8],
+    cursor: usize,
+    checks: &mut Checks,
+) -> Result<(), RowError> {
+    let mut cursor = cursor;
+
+    while cursor < region.len() {
+        let end_a = cursor + 64.min(region.len() - cursor);
+        let window_a = &region[cursor..end_a];
+        let (mut semi_a, mut nl_a) = I::find_delimiters64(window_a, checks.delims);
+
+        if nl_a == 0 {
+            cursor =
+                unsafe { process_long_line::<I, P, T>(region, tbl, cursor, region.len(), checks)? };
+            continue;
+        }
+
+        let mut line_cursor_a = 0;
+
+        while nl_a != 0 {
+            line_cursor_a = unsafe {
+                process_line::<I, R, P, T>(window_a, tbl, line_cursor_a, semi_a, nl_a, checks)?
+            };
+            semi_a &= !(nl_a ^ (nl_a - 1));
+            nl_a &= nl_a - 1;
+        }
+
+        cursor += line_cursor_a;
+    }
+
+    // Rows end one past their newline, or one past the end of the region for the final row, so
+    // stopping exactly at the end means the region ends in an empty row.
+    if cursor == region.len() && !region.is_empty() {
+        checks.reject(RowError::MissingSeparator, &region[cursor..])?;
+    }
+
+    Ok(())
+}
+
+#[inline(always)]
+unsafe fn process_long_line<I: Isa, P: Temps, T: Temperature>(
+    buf: &[u8],
+    tbl: &mut Table<T>,
+    start: usize,
+    end: usize,
+    checks: &mut Checks,
+) -> Result<usize, RowError> {
+    let nl_pos = buf[start..end]
+        .byte_position(checks.delims.terminator)
+        .unwrap_or(end - start);
+    let row = &buf[start..start + nl_pos];
+
+    let (name, temps) = match check_row_shape(row, checks) {
+        Ok((name, temps)) => (name, temps.map(T::from_i32)),
+        Err(kind) => {
+            checks.reject(kind, row)?;
+            return Ok(start + nl_pos + 1);
+        }
+    };
+
+    let (hash, prefix) = Table::hash(&name);
+    let slot = I::lookup_exact(tbl, hash, prefix, &name);
+    let columns = tbl.columns();
+    tbl.update_columns(slot, hash, prefix, &name, &temps[..columns]);
+
+    Ok(start + nl_pos + 1)
+}
+
+#[inline(always)]
+unsafe fn process_line<I: Isa, R: Rows, P: Temps, T: Temperature>(
+    buf: &[u8],
+    tbl: &mut Table<T>,
+    start: usize,
+    semi: u64,
+    nl: u64,
+    checks: &mut Checks,
+) -> Result<usize, RowError> {
+    if !R::PLAIN {
+        return unsafe { process_columns::<I, P, T>(buf, tbl, start, semi, nl, checks) };
+    }
+
+    let semi_pos = semi.trailing_zeros() as usize;
+    let nl_pos = nl.trailing_zeros() as usize;
+
+    let precision = checks.precision;
+    let temp_len = temp_len_range::<P>(precision, checks.delims);
+
+    if is_malformed(start, semi_pos, nl_pos, checks.max_name_len, temp_len) {
+        let row = &buf[start..nl_pos];
+        let kind = check_row_shape(row, checks).unwrap_err();
+        checks.reject(kind, row)?;
+        return Ok(nl_pos + 1);
+    }
+
+    let name = unsafe { buf.get_unchecked(start..semi_pos) };
+    let temp = unsafe { temp_slice(buf, semi_pos, nl_pos, checks.delims) };
+
+    let (parsed_temp, ok) = unsafe { P::parse(temp, precision) };
+    if !ok {
+        checks.reject(RowError::BadTemperature, &buf[start..nl_pos])?;
+        return Ok(nl_pos + 1);
+    }
+    let parsed_temp = T::from_i32(parsed_temp);
+
+    let (hash, prefix) = Table::hash(name);
+    tbl.prefetch(hash);
+
+    let slot = I::lookup_exact(tbl, hash, prefix, name);
+    tbl.update(slot, hash, prefix, name, parsed_temp);
+
+    Ok(nl_pos + 1)
+}
+
+/// Like `process_line`, for rows laid out by `checks.schema`, or with tracking. Walks the row's
+/// separators in `semi` to find its name and value columns.
+#[inline(always)]
+unsafe fn process_columns<I: Isa, P: Temps, T: Temperature>(
+    buf: &[u8],
+    tbl: &mut Table<T>,
+    start: usize,
+    semi: u64,
+    nl: u64,
+    checks: &mut Checks,
+) -> Result<usize, RowError> {
+    let schema = checks.schema;
+    let nl_pos = nl.trailing_zeros() as usize;
+    let row = &buf[start..nl_pos];
+
+    let cr = checks.delims.crlf && row.last() == Some(&b'\r');
+    let end = nl_pos - cr as usize;
+
+    // Separators in the row, ignoring any past the last column's start.
+    let mut separators = semi & ((1 << end) - 1);
+    let mut keys = [(start, start); Schema::MAX_KEYS];
+    let mut temps = [T::default(); Schema::MAX_VALUES];
+    let (mut key_fields, mut values) = (0, 0);
+    let mut column_start = start;
+
+    let (min_temp_len, max_temp_len) = P::len_range(checks.precision);
+
+    // Any failure is left to `check_row_shape` to name.
+    let malformed = 'columns: {
+        if (separators.count_ones() as usize) < schema.columns - 1 {
+            break 'columns true;
+        }
+
+        for column in 0..=schema.last_needed() {
+            let column_end = if column == schema.columns - 1 {
+                end
+            } else {
+                separators.trailing_zeros() as usize
+            };
+
+            if schema.keys >> column & 1 == 1 {
+                keys[key_fields] = (column_start, column_end);
+                key_fields += 1;
+            } else if schema.values >> column & 1 == 1 {
+                let temp = column_start..column_end;
+                if !(min_temp_len..=max_temp_len).contains(&temp.len()) {
+                    break 'columns true;
+                }
+
+                let (parsed_temp, ok) =
+                    unsafe { P::parse(buf.get_unchecked(temp), checks.precision) };
+                if !ok {
+                    break 'columns true;
+                }
+
+                temps[values] = T::from_i32(parsed_temp);
+                values += 1;
+            }
+
+            separators &= separators.wrapping_sub(1);
+            column_start = column_end + 1;
+        }
+
+        // Joined by one separator each.
+        let fields_len: usize = keys[..key_fields].iter().map(|(s, e)| e - s + 1).sum();
+        let name_len = fields_len - 1;
+        name_len == 0 || name_len > checks.max_name_len
+    };
+
+    if malformed {
+        let kind = check_row_shape(row, checks).unwrap_err();
+        checks.reject(kind, row)?;
+        return Ok(nl_pos + 1);
+    }
+
+    let name = if schema.keys_adjacent() {
+        &buf[keys[0].0..keys[key_fields - 1].1]
+    } else {
+        let fields = keys[..key_fields].iter().map(|&(s, e)| &buf[s..e]);
+        Table::composite_key(fields, checks.delims.separator, &mut checks.key)
+    };
+
+    let (hash, prefix) = Table::hash(name);
+    let slot = I::lookup_exact(tbl, hash, prefix, name);
+    tbl.update_columns(slot, hash, prefix, name, &temps[..values]);
+
+    Ok(nl_pos + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Guarded;
+    use std::collections::BTreeMap;
+
+    /// Longer than a 64-byte window, so its rows are read by `process_long_line`.
+    const LONG_NAME: &str =
+        "Llanfairpwllgwyngyllgogerychwyrndrobwllllantysiliogogogoch Station Seventy";
+
+    fn reference(input: &str) -> BTreeMap<String, (i32, i64, u64, i32)> {
+        reference_at(input, Precision::TENTHS)
+    }
+
+    fn reference_at(input: &str, precision: Precision) -> BTreeMap<String, (i32, i64, u64, i32)> {
+        let mut stations = BTreeMap::new();
+        let scale = 10f64.powi(precision.decimals as i32);
+
+        for line in input.lines() {
+            let (name, temp) = line.split_once(';').unwrap();
+            let temp = (temp.parse::<f64>().unwrap() * scale).round() as i32;
+
+            stations
+                .entry(name.to_string())
+                .and_modify(|(min, sum, count, max): &mut (i32, i64, u64, i32)| {
+                    *min = (*min).min(temp);
+                    *sum += temp as i64;
+                    *count += 1;
+                    *max = (*max).max(temp);
+                })
+                .or_insert((temp, temp as i64, 1, temp));
+        }
+
+        stations
+    }
+
+    fn collect(results: &Results) -> BTreeMap<String, (i32, i64, u64, i32)> {
+        results
+            .entries()
+            .into_iter()
+            .map(|(name, e)| {
+                let name = str::from_utf8(name).unwrap().to_string();
+                (name, (e.min, e.sum, e.count, e.max))
+            })
+            .collect()
+    }
+
+    fn assert_matches_reference(input: &str) {
+        assert_eq!(
+            collect(&aggregate(input.as_bytes()).unwrap()),
+            reference(input)
+        );
+    }
+
+    /// Aggregates `buf` with `config` on every supported backend, with one and several threads.
+    /// Each outcome is labelled with its backend and thread count.
+    fn aggregate_all(buf: &[u8], config: &Config) -> Vec<(String, Result<Results, Error>)> {
+        let mut outcomes = Vec::new();
+
+        for backend in [Backend::Avx512, Backend::Avx2, Backend::Sse2] {
+            if !backend.is_supported() {
+                continue;
+            }
+
+            for threads in [1, 3] {
+                let config = Config {
+                    threads,
+                    backend,
+                    ..*config
+                };
+                let label = format!("{backend:?}, threads = {threads}");
+                outcomes.push((label, aggregate_with(buf, &config)));
+            }
+        }
+
+        outcomes
+    }
+
+    /// Checks every supported backend, with one and several threads, against `input` in the
+    /// 1BRC format.
+    fn assert_matches_reference_all(buf: &[u8], input: &str, config: &Config) {
+        let expected = reference_at(input, config.precision);
+
+        for (label, results) in aggregate_all(buf, config) {
+            assert_eq!(collect(&results.unwrap()), expected, "{label}");
+        }
+    }
+
+    /// Checks every supported backend, with one and several threads, fails with `expected`.
+    fn assert_error_all(buf: &[u8], config: &Config, expected: &str) {
+        for (label, results) in aggregate_all(buf, config) {
+            let err = results.err().expect(expected);
+            assert_eq!(err.to_string(), expected, "{label}");
+        }
+    }
+
+    #[test]
+    fn test_aggregate_errors() {
+        let cases: &[(&[u8], &str)] = &[
+            (
+                b"Ab;1.0\nCd\nEf;2.0\n",
+                "line 2 (byte 7): missing separator",
+            ),
+            (b"Ab;1.0\n\nEf;2.0\n", "line 2 (byte 7): missing separator"),
+            (b"Ab;1.0\nCd;2.0\nEf", "line 3 (byte 14): missing separator"),
+            (b"Ab;1.0\nCd;100.00\n", "line 2 (byte 7): bad temperature"),
+            (b"Ab;1.0\nCd;100.0\n", "line 2 (byte 7): bad temperature"),
+            (b"Ab;1.0\nCd;1.25\n", "line 2 (byte 7): bad temperature"),
+            (
+                b"Ab;1.0\nCd;abc\nEf;2.0\n",
+                "line 2 (byte 7): bad temperature",
+            ),
+            (b"Ab;1.0\nCd;\nEf;2.0\n", "line 2 (byte 7): bad temperature"),
+            (
+                b"Ab;1.0\nC;dd;1.0\nEf;2.0\n",
+                "line 2 (byte 7): bad temperature",
+            ),
+            (
+                b"Ab;1.0\n\xff\xfe;2.0\n",
+                "line 2 (byte 7): station name is not valid UTF-8",
+            ),
+            (b"Ab;1.0\n;2.0\n", "line 2 (byte 7): station name is empty"),
+            (b"Ab;1.0\n;\n", "line 2 (byte 7): station name is empty"),
+        ];
+
+        for &(input, expected) in cases {
+            assert_error_all(input, &Config::default(), expected);
+        }
+
+        let config = Config {
+            max_name_len: 4,
+            ..Config::default()
+        };
+        assert_error_all(
+            b"Ab;1.0\nHamburg;2.0\n",
+            &config,
+            "line 2 (byte 7): station name too long",
+        );
+    }
+
+    #[test]
+    fn test_aggregate_config_errors() {
+        let hundredths = Precision {
+            decimals: 2,
+            int_digits: 3,
+        };
+        let cases = [
+            Config {
+                precision: Precision {
+                    decimals: 0,
+                    int_digits: 2,
+                },
+                ..Config::default()
+            },
+            Config {
+                precision: Precision {
+                    decimals: 5,
+                    int_digits: 5,
+                },
+                ..Config::default()
+            },
+            Config {
+                delimiters: Delimiters {
+                    separator: b'\n',
+                    ..Delimiters::default()
+                },
+                ..Config::default()
+            },
+            Config {
+                schema: Schema {
+                    columns: 2,
+                    keys: 1 << 0,
+                    values: 1 << 0,
+                },
+                ..Config::default()
+            },
+            Config {
+                schema: Schema {
+                    columns: 2,
+                    keys: 1 << 0,
+                    values: 1 << 2,
+                },
+                ..Config::default()
+            },
+            Config {
+                precision: hundredths,
+                tracking: Tracking {
+                    histograms: true,
+                    ..Tracking::default()
+                },
+                ..Config::default()
+            },
+        ];
+        let expected = [
+            ConfigError::UnsupportedPrecision(cases[0].precision),
+            ConfigError::UnsupportedPrecision(cases[1].precision),
+            ConfigError::UnsupportedDelimiters(cases[2].delimiters),
+            ConfigError::UnsupportedSchema,
+            ConfigError::UnsupportedSchema,
+            ConfigError::HistogramsTooPrecise(hundredths),
+        ];
+
+        for (config, expected) in cases.iter().zip(expected) {
+            assert_eq!(config.check(), Err(expected));
+            match aggregate_with(b"Ab;1.0\n", config) {
+                Err(Error::Config(err)) => assert_eq!(err, expected),
+                other => panic!("expected {expected:?}, got {:?}", other.map(|_| ())),
+            }
+        }
+
+        for backend in [Backend::Avx512, Backend::Avx2, Backend::Sse2] {
+            let config = Config {
+                backend,
+                ..Config::default()
+            };
+            let expected = match backend.is_supported() {
+                true => Ok(()),
+                false => Err(ConfigError::UnsupportedBackend(backend)),
+            };
+            assert_eq!(config.check(), expected);
+        }
+    }
+
+    #[test]
+    fn test_aggregate_reports_first_error() {
+        let rows: Vec<String> = (0..3_000)
+            .map(|i| match i {
+                1_234 => format!("{LONG_NAME} {i}"),
+                2_000 => "Ab;1.0;2.0".to_string(),
+                _ => format!("Station {};{}.{}", i % 97, i % 50, i % 10),
+            })
+            .collect();
+
+        let input = rows.join("\n");
+        let offset: usize = rows[..1_234].iter().map(|row| row.len() + 1).sum();
+
+        assert_error_all(
+            input.as_bytes(),
+            &Config::default(),
+            &format!("line 1235 (byte {offset}): missing separator"),
+        );
+
+        let config = Config {
+            max_name_len: 5,
+            ..Config::default()
+        };
+        assert_error_all(
+            input.as_bytes(),
+            &config,
+            "line 1 (byte 0): station name too long",
+        );
+    }
+
+    #[test]
+    fn test_aggregate_empty_names() {
+        // Enough to grow the table many times over, were each one to claim a new slot.
+        let input = "Ab;1.0\n;2.0\n".repeat(100_000);
+
+        let mut config = Config {
+            threads: 3,
+            ..Config::default()
+        };
+        let err = aggregate_with(input.as_bytes(), &config).err().unwrap();
+        assert_eq!(err.to_string(), "line 2 (byte 7): station name is empty");
+
+        for policy in [Policy::Skip, Policy::Quarantine] {
+            config.policy = policy;
+            let results = aggregate_with(input.as_bytes(), &config).unwrap();
+            assert_eq!(collect(&results), reference(&"Ab;1.0\n".repeat(100_000)));
+            assert_eq!(results.skipped(), 100_000, "{policy:?}");
+
+            let quarantined = results.quarantined();
+            if policy == Policy::Skip {
+                assert!(quarantined.is_empty());
+                continue;
+            }
+
+            assert_eq!(quarantined.len(), 100_000);
+            for (i, rejected) in quarantined.iter().enumerate() {
+                assert_eq!(rejected.error.offset(), Some(i * 12 + 7));
+                assert_eq!(rejected.error.line(), Some(i * 2 + 2));
+                assert_eq!(rejected.row, b";2.0");
+            }
+            assert_eq!(
+                quarantined[0].error.to_string(),
+                "line 2 (byte 7): station name is empty"
+            );
+        }
+    }
+
+    #[test]
+    fn test_precision_parse() {
+        let precision = Precision {
+            decimals: 2,
+            int_digits: 3,
+        };
+
+        let cases: &[(&[u8], Option<i32>)] = &[
+            (b"0.00", Some(0)),
+            (b"-0.01", Some(-1)),
+            (b"1.25", Some(125)),
+            (b"123.45", Some(12345)),
+            (b"-999.99", Some(-99999)),
+            (b"1.2", None),
+            (b"1.234", None),
+            (b"1234.56", None),
+            (b".25", None),
+            (b"-.25", None),
+            (b"1,25", None),
+            (b"1.2a", None),
+            (b"", None),
+        ];
+
+        for &(bytes, expected) in cases {
+            assert_eq!(
+                precision.parse(bytes),
+                expected,
+                "{:?}",
+                bytes.escape_ascii()
+            );
+        }
+    }
+
+    #[test]
+    fn test_precision_degrees() {
+        let hundredths = Precision {
+            decimals: 2,
+            int_digits: 2,
+        };
+        let millis = Precision {
+            decimals: 3,
+            int_digits: 3,
+        };
+
+        assert_eq!(Precision::TENTHS.degrees(0).to_string(), "0.0");
+        assert_eq!(Precision::TENTHS.degrees(-5).to_string(), "-0.5");
+        assert_eq!(Precision::TENTHS.degrees(-999).to_string(), "-99.9");
+        assert_eq!(hundredths.degrees(-1).to_string(), "-0.01");
+        assert_eq!(millis.degrees(5).to_string(), "0.005");
+        assert_eq!(millis.degrees(-123456).to_string(), "-123.456");
+        assert_eq!(
+            Precision::TENTHS.degrees(i64::MIN).to_string(),
+            "-922337203685477580.8"
+        );
+
+        // Values with exactly `decimals` places print the same as floats.
+        for precision in [Precision::TENTHS, hundredths, millis] {
+            let decimals = precision.decimals as usize;
+            let scale = 10f64.powi(decimals as i32);
+            for units in -20_000..20_000 {
+                assert_eq!(
+                    precision.degrees(units).to_string(),
+                    format!("{:.decimals$}", units as f64 / scale)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregate_precision() {
+        let precisions = [
+            // Still fits an `i16`.
+            Precision {
+                decimals: 2,
+                int_digits: 2,
+            },
+            Precision {
+                decimals: 3,
+                int_digits: 3,
+            },
+        ];
+
+        for precision in precisions {
+            let max = 10i64.pow(precision.decimals + precision.int_digits) - 1;
+            let decimals = precision.decimals as usize;
+
+            let rows: Vec<String> = (0..3_000i64)
+                .map(|i| {
+                    let value = (i * 7_919 % (2 * max + 1)) - max;
+                    let degrees = value as f64 / 10f64.powi(decimals as i32);
+                    format!("Station {};{degrees:.decimals$}", i % 97)
+                })
+                .collect();
+            let input = rows.join("\n");
+
+            let config = Config {
+                precision,
+                ..Config::default()
+            };
+            assert_matches_reference_all(input.as_bytes(), &input, &config);
+            assert_error_all(b"Ab;1.0\n", &config, "line 1 (byte 0): bad temperature");
+        }
+    }
+
+    #[test]
+    fn test_aggregate_schema() {
+        let rows: Vec<(String, String)> = (0..3_000)
+            .map(|i| match i % 41 {
+                0 => (
+                    format!("{LONG_NAME} {}", i % 3),
+                    format!("{}.{}", i % 100, i % 10),
+                ),
+                _ => (
+                    format!("Station {}", i % 97),
+                    format!("-{}.{}", i % 100, i % 10),
+                ),
+            })
+            .collect();
+        let input: Vec<String> = rows
+            .iter()
+            .map(|(name, temp)| format!("{name};{temp}"))
+            .collect();
+        let input = input.join("\n");
+
+        let csv_crlf = Delimiters {
+            separator: b',',
+            terminator: b'\n',
+            crlf: true,
+        };
+        let cases = [
+            (
+                Schema {
+                    columns: 4,
+                    keys: 1 << 0,
+                    values: 1 << 2,
+                },
+                Delimiters::default(),
+                "\n",
+                &(|name: &str, temp: &str| format!("{name};2024-01-01T00:00:00Z;{temp};0.5;x"))
+                    as &dyn Fn(&str, &str) -> String,
+            ),
+            (
+                Schema {
+                    columns: 3,
+                    keys: 1 << 2,
+                    values: 1 << 0,
+                },
+                csv_crlf,
+                "\r\n",
+                &|name: &str, temp: &str| format!("{temp},,{name}"),
+            ),
+        ];
+
+        for (schema, delims, terminator, row) in cases {
+            let rows: Vec<String> = rows.iter().map(|(name, temp)| row(name, temp)).collect();
+            let buf = rows.join(terminator);
+
+            let config = Config {
+                delimiters: delims,
+                schema,
+                ..Config::default()
+            };
+            assert_matches_reference_all(buf.as_bytes(), &input, &config);
+        }
+
+        let config = Config {
+            schema: Schema {
+                columns: 3,
+                keys: 1 << 0,
+                values: 1 << 1,
+            },
+            max_name_len: 4,
+            ..Config::default()
+        };
+
+        for (input, expected) in [
+            (
+                &b"Ab;1.0;x\nAb;1.0\n"[..],
+                "line 2 (byte 9): missing separator",
+            ),
+            (
+                b"Ab;1.0;x\nAbcde;1.0;x\n",
+                "line 2 (byte 9): station name too long",
+            ),
+            (
+                b"Ab;1.0;x\n;1.0;x\n",
+                "line 2 (byte 9): station name is empty",
+            ),
+            (b"Ab;1.0;x\nAb;1.00;x\n", "line 2 (byte 9): bad temperature"),
+            (b"Ab;1.0;x\nAb;1.0x;\n", "line 2 (byte 9): bad temperature"),
+        ] {
+            let err = aggregate_with(input, &config).err().unwrap();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        // The last column takes the rest of the row.
+        let results = aggregate_with(b"Ab;1.0;x;y\nAb;2.0;\n", &config).unwrap();
+        assert_eq!(collect(&results), reference("Ab;1.0\nAb;2.0"));
+    }
+
+    #[test]
+    fn test_aggregate_value_columns() {
+        let rows: Vec<(String, [String; 3])> = (0..3_000)
+            .map(|i| {
+                let name = match i % 41 {
+                    0 => format!("{LONG_NAME} {}", i % 3),
+                    _ => format!("Station {}", i % 97),
+                };
+                let temps = [
+                    format!("{}.{}", i % 100, i % 10),
+                    format!("-{}.{}", i % 37, i % 7),
+                    format!("{}.{}", 99 - i % 100, i % 3),
+                ];
+                (name, temps)
+            })
+            .collect();
+
+        let row = |(name, [a, b, c]): &(String, [String; 3])| format!("{name};{a};x;{b};{c}");
+        let buf = rows.iter().map(row).collect::<Vec<_>>().join("\r\n");
+
+        let expected: Vec<_> = (0..3)
+            .map(|column| {
+                let rows = rows
+                    .iter()
+                    .map(|(name, temps)| format!("{name};{}", temps[column]));
+                reference(&rows.collect::<Vec<_>>().join("\n"))
+            })
+            .collect();
+
+        let schema = Schema {
+            columns: 5,
+            keys: 1 << 0,
+            values: 1 << 1 | 1 << 3 | 1 << 4,
+        };
+        let delimiters = Delimiters {
+            crlf: true,
+            ..Delimiters::default()
+        };
+
+        let config = Config {
+            delimiters,
+            schema,
+            ..Config::default()
+        };
+
+        for (label, results) in aggregate_all(buf.as_bytes(), &config) {
+            let results = results.unwrap();
+            let entries = results.entries_columns();
+
+            for (column, expected) in expected.iter().enumerate() {
+                let actual: BTreeMap<_, _> = entries
+                    .iter()
+                    .map(|(name, columns)| {
+                        let e = columns.column(column);
+                        let name = str::from_utf8(name).unwrap().to_string();
+                        (name, (e.min, e.sum, e.count, e.max))
+                    })
+                    .collect();
+                assert_eq!(&actual, expected, "column {column}, {label}");
+            }
+
+            // `entries` reports the first value column.
+            assert_eq!(collect(&results), expected[0], "{label}");
+        }
+
+        assert_error_all(
+            b"Ab;1.0;x;2.0;3.0\nAb;1.0;x;2.0;3.00\n",
+            &config,
+            "line 2 (byte 17): bad temperature",
+        );
+    }
+
+    #[test]
+    fn test_aggregate_composite_keys() {
+        let rows: Vec<[String; 4]> = (0..3_000)
+            .map(|i| {
+                let station = match i % 41 {
+                    0 => format!("{LONG_NAME} {}", i % 3),
+                    _ => format!("Station {}", i % 97),
+                };
+                let month = format!("2024-{:02}", i % 12 + 1);
+                let temp = format!("-{}.{}", i % 100, i % 10);
+                let region = format!("Region {}", i % 5);
+                [station, month, temp, region]
+            })
+            .collect();
+        let buf = rows.iter().map(|row| row.join(";")).collect::<Vec<_>>();
+        let buf = buf.join("\r\n");
+
+        let delimiters = Delimiters {
+            crlf: true,
+            ..Delimiters::default()
+        };
+
+        // Adjacent key columns, then ones with the temperature between them.
+        for keys in [[0, 1], [0, 3]] {
+            let schema = Schema {
+                columns: 4,
+                keys: 1 << keys[0] | 1 << keys[1],
+                values: 1 << 2,
+            };
+            let input = rows.iter().map(|row| {
+                let [a, b] = keys.map(|column| &row[column]);
+                format!("{a}|{b};{}", row[2])
+            });
+            let input = input.collect::<Vec<_>>().join("\n");
+
+            let config = Config {
+                delimiters,
+                schema,
+                ..Config::default()
+            };
+
+            for (label, results) in aggregate_all(buf.as_bytes(), &config) {
+                let results = results.unwrap();
+                let actual: BTreeMap<_, _> = results
+                    .entries()
+                    .into_iter()
+                    .map(|(name, e)| {
+                        let fields: Vec<_> = results
+                            .key_fields(name)
+                            .map(|field| str::from_utf8(field).unwrap())
+                            .collect();
+                        (fields.join("|"), (e.min, e.sum, e.count, e.max))
+                    })
+                    .collect();
+                assert_eq!(actual, reference(&input), "{keys:?}, {label}");
+            }
+        }
+
+        // The joined key counts towards `max_name_len`.
+        let config = Config {
+            schema: Schema {
+                columns: 3,
+                keys: 1 << 0 | 1 << 2,
+                values: 1 << 1,
+            },
+            max_name_len: 5,
+            ..Config::default()
+        };
+        assert!(aggregate_with(b"Ab;1.0;Cd\n", &config).is_ok());
+        let err = aggregate_with(b"Ab;1.0;Cd\nAb;1.0;Cde\n", &config)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "line 2 (byte 10): station name too long");
+    }
+
+    #[test]
+    fn test_aggregate_histograms() {
+        let rows: Vec<(String, [i32; 2])> = (0..3_000)
+            .map(|i| {
+                let name = match i % 41 {
+                    0 => format!("{LONG_NAME} {}", i % 3),
+                    _ => format!("Station {}", i % 97),
+                };
+                (name, [(i * 37) % 1999 - 999, -(i % 113)])
+            })
+            .collect();
+
+        let tenths = |t: i32| {
+            format!(
+                "{}{}.{}",
+                if t < 0 { "-" } else { "" },
+                t.abs() / 10,
+                t.abs() % 10
+            )
+        };
+        let row =
+            |(name, [a, b]): &(String, [i32; 2])| format!("{name};{};{}", tenths(*a), tenths(*b));
+        let input = rows.iter().map(row).collect::<Vec<_>>().join("\n");
+
+        let mut temps: BTreeMap<String, [Vec<i32>; 2]> = BTreeMap::new();
+        for (name, [a, b]) in &rows {
+            let columns = temps.entry(name.clone()).or_default();
+            columns[0].push(*a);
+            columns[1].push(*b);
+        }
+
+        // Nearest rank over the sorted temperatures.
+        let quantiles = [0.0, 0.5, 0.9, 0.99, 1.0];
+        let expected: BTreeMap<_, _> = temps
+            .into_iter()
+            .map(|(name, mut columns)| {
+                let columns = columns.each_mut().map(|temps| {
+                    temps.sort_unstable();
+                    let rank = |q: f64| ((q * temps.len() as f64).ceil() as usize).max(1);
+                    quantiles.map(|q| temps[rank(q) - 1])
+                });
+                (name, columns)
+            })
+            .collect();
+
+        let schema = Schema {
+            columns: 3,
+            keys: 1 << 0,
+            values: 1 << 1 | 1 << 2,
+        };
+
+        let config = Config {
+            schema,
+            tracking: Tracking {
+                histograms: true,
+                ..Tracking::default()
+            },
+            ..Config::default()
+        };
+
+        for (label, results) in aggregate_all(input.as_bytes(), &config) {
+            let actual: BTreeMap<_, _> = results
+                .unwrap()
+                .histograms()
+                .into_iter()
+                .map(|(name, histograms)| {
+                    let columns = [0, 1]
+                        .map(|column| quantiles.map(|q| histograms[column].quantile(q).unwrap()));
+                    (str::from_utf8(name).unwrap().to_string(), columns)
+                })
+                .collect();
+            assert_eq!(actual, expected, "{label}");
+        }
+
+        // Only tracked when asked for, and the stats are the same either way.
+        let input = rows
+            .iter()
+            .map(|(name, [a, _])| format!("{name};{}", tenths(*a)));
+        let input = input.collect::<Vec<_>>().join("\n");
+        let config = Config {
+            tracking: Tracking {
+                histograms: true,
+                ..Tracking::default()
+            },
+            ..Config::default()
+        };
+        let tracked = aggregate_with(input.as_bytes(), &config).unwrap();
+        let untracked = aggregate_with(input.as_bytes(), &Config::default()).unwrap();
+        assert_eq!(tracked.entries(), untracked.entries());
+        assert_eq!(tracked.histograms().len(), expected.len());
+        assert!(untracked.histograms().is_empty());
+    }
+
+    #[test]
+    fn test_aggregate_squares() {
+        let precisions = [
+            Precision::TENTHS,
+            // Needs `i32` entries, and squares past 64 bits over enough rows.
+            Precision {
+                decimals: 4,
+                int_digits: 5,
+            },
+        ];
+
+        for precision in precisions {
+            let max = 10i64.pow(precision.decimals + precision.int_digits) - 1;
+            let decimals = precision.decimals as usize;
+
+            let rows: Vec<(String, i64)> = (0..3_000i64)
+                .map(|i| {
+                    (
+                        format!("Station {}", i % 97),
+                        (i * 7_919 % (2 * max + 1)) - max,
+                    )
+                })
+                .collect();
+            let input = rows.iter().map(|(name, value)| {
+                let degrees = *value as f64 / 10f64.powi(decimals as i32);
+                format!("{name};{degrees:.decimals$}")
+            });
+            let input = input.collect::<Vec<_>>().join("\n");
+
+            let mut expected: BTreeMap<String, u128> = BTreeMap::new();
+            for (name, value) in &rows {
+                *expected.entry(name.clone()).or_default() += (value * value) as u128;
+            }
+
+            let config = Config {
+                precision,
+                tracking: Tracking {
+                    squares: true,
+                    ..Tracking::default()
+                },
+                ..Config::default()
+            };
+
+            for (label, results) in aggregate_all(input.as_bytes(), &config) {
+                let results = results.unwrap();
+                let actual: BTreeMap<_, _> = results
+                    .squares()
+                    .into_iter()
+                    .map(|(name, squares)| (str::from_utf8(name).unwrap().to_string(), squares[0]))
+                    .collect();
+                assert_eq!(actual, expected, "{precision:?}, {label}");
+            }
+
+            // Against a two-pass variance of the same values.
+            let results = aggregate_with(input.as_bytes(), &config).unwrap();
+            for ((name, entry), (_, squares)) in results.entries().iter().zip(results.squares()) {
+                let name = str::from_utf8(name).unwrap();
+                let values: Vec<f64> = rows
+                    .iter()
+                    .filter(|(station, _)| station == name)
+                    .map(|&(_, value)| value as f64)
+                    .collect();
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let variance =
+                    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+
+                let actual = entry.variance(squares[0]);
+                assert!(
+                    (actual - variance).abs() <= variance * 1e-12,
+                    "{name}: {actual} != {variance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregate_delimiters() {
+        let rows: Vec<String> = (0..3_000)
+            .map(|i| match i % 41 {
+                0 => format!("{LONG_NAME} {};{}.{}", i % 3, i % 100, i % 10),
+                _ => format!("Station {};-{}.{}", i % 97, i % 100, i % 10),
+            })
+            .collect();
+        let input = rows.join("\n");
+
+        let csv = Delimiters {
+            separator: b',',
+            ..Delimiters::default()
+        };
+        let tsv = Delimiters {
+            separator: b'\t',
+            ..Delimiters::default()
+        };
+        let crlf = Delimiters {
+            crlf: true,
+            ..Delimiters::default()
+        };
+        let csv_crlf = Delimiters { crlf: true, ..csv };
+        let scaled = Precision {
+            decimals: 1,
+            int_digits: 3,
+        };
+
+        for (delims, precision) in [
+            (csv, Precision::TENTHS),
+            (tsv, Precision::TENTHS),
+            (crlf, Precision::TENTHS),
+            (csv_crlf, Precision::TENTHS),
+            (csv_crlf, scaled),
+        ] {
+            let separator = (delims.separator as char).to_string();
+            let terminator = if delims.crlf { "\r\n" } else { "\n" };
+            let buf = input.replace(';', &separator).replace('\n', terminator);
+
+            let config = Config {
+                precision,
+                delimiters: delims,
+                ..Config::default()
+            };
+            assert_matches_reference_all(buf.as_bytes(), &input, &config);
+        }
+
+        // CRLF stripping still accepts bare terminators, but only strips one `\r`.
+        let config = Config {
+            delimiters: crlf,
+            ..Config::default()
+        };
+        let results = aggregate_with(b"Ab;1.0\r\nAb;2.0\nAb;3.0\r", &config).unwrap();
+        assert_eq!(collect(&results), reference("Ab;1.0\nAb;2.0\nAb;3.0"));
+
+        for input in [&b"Ab;1.0\r\r\n"[..], b"Ab;1.\r\n", b"\r\n"] {
+            assert!(aggregate_with(input, &config).is_err(), "{input:?}");
+        }
+
+        // Without it, a `\r` is part of the temperature.
+        let err = aggregate(b"Ab;1.0\r\n").err().unwrap();
+        assert_eq!(err.to_string(), "line 1 (byte 0): bad temperature");
+    }
+
+    #[test]
+    fn test_aggregate_skip_and_quarantine() {
+        let bad_rows: &[(usize, &[u8], &str)] = &[
+            (0, b"", "missing separator"),
+            (17, b"Ab;1.0;2.0", "bad temperature"),
+            (500, b";1.0", "station name is empty"),
+            (1_000, LONG_NAME.as_bytes(), "missing separator"),
+            (1_001, b"Cd;", "bad temperature"),
+            (1_500, b"\xff\xfe;2.0", "station name is not valid UTF-8"),
+            (2_222, b"", "missing separator"),
+            (2_500, b";-12.3", "station name is empty"),
+            (2_997, b";", "station name is empty"),
+            (2_998, b"\xff\xfe;-3.5", "station name is not valid UTF-8"),
+            (2_999, b"Ef", "missing separator"),
+        ];
+
+        let rows: Vec<Vec<u8>> = (0..3_000)
+            .map(|i| match bad_rows.iter().find(|&&(at, _, _)| at == i) {
+                Some(&(_, row, _)) => row.to_vec(),
+                None => format!("Station {};{}.{}", i % 97, i % 50, i % 10).into_bytes(),
+            })
+            .collect();
+
+        let buf = rows.join(&b'\n');
+        let good: Vec<&str> = rows
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !bad_rows.iter().any(|&(at, _, _)| at == *i))
+            .map(|(_, row)| str::from_utf8(row).unwrap())
+            .collect();
+        let expected = reference(&good.join("\n"));
+
+        let offsets: Vec<usize> = rows
+            .iter()
+            .scan(0, |offset, row| {
+                let start = *offset;
+                *offset += row.len() + 1;
+                Some(start)
+            })
+            .collect();
+
+        let expected_quarantined: Vec<_> = bad_rows
+            .iter()
+            .map(|&(i, row, message)| {
+                let message = format!("line {} (byte {}): {message}", i + 1, offsets[i]);
+                (message, row)
+            })
+            .collect();
+
+        for policy in [Policy::Skip, Policy::Quarantine] {
+            let config = Config {
+                policy,
+                ..Config::default()
+            };
+
+            for (label, results) in aggregate_all(&buf, &config) {
+                let results = results.unwrap();
+                assert_eq!(collect(&results), expected, "{policy:?}, {label}");
+                assert_eq!(
+                    results.skipped(),
+                    bad_rows.len() as u64,
+                    "{policy:?}, {label}"
+                );
+
+                let quarantined: Vec<_> = results
+                    .quarantined()
+                    .iter()
+                    .map(|rejected| (rejected.error.to_string(), rejected.row.as_slice()))
+                    .collect();
+                match policy {
+                    Policy::Quarantine => assert_eq!(quarantined, expected_quarantined, "{label}"),
+                    _ => assert!(quarantined.is_empty(), "{label}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_temp() {
+        let cases: &[(&[u8], i16)] = &[
+            (b"0.0", 0),
+            (b"1.2", 12),
+            (b"-1.2", -12),
+            (b"12.3", 123),
+            (b"-12.3", -123),
+            (b"99.9", 999),
+            (b"-99.9", -999),
+        ];
+
+        for &(bytes, expected) in cases {
+            let mut padded = bytes.to_vec();
+            padded.extend_from_slice(b"\nXXXXXXXX");
+            assert_eq!(parse_temp(&padded[..bytes.len()]), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_temp_at_end_of_mapping() {
+        for (bytes, expected) in [(&b"1.2"[..], 12), (b"-1.2", -12), (b"-99.9", -999)] {
+            let guarded = Guarded::new(bytes);
+            assert_eq!(parse_temp(guarded.as_slice()), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_temp_checked_all_values() {
+        for tenths in -999..=999 {
+            let value = tenths as f64 / 10.0;
+            let mut inputs = vec![format!("{value:.1}")];
+            if (-99..=99).contains(&tenths) {
+                // Single-digit values, also written with a leading zero.
+                inputs.push(format!(
+                    "{}0{:.1}",
+                    if tenths < 0 { "-" } else { "" },
+                    value.abs()
+                ));
+            }
+
+            for input in inputs {
+                let expected = (input.parse::<f64>().unwrap() * 10.0).round() as i16;
+                assert_eq!(
+                    parse_temp_checked(input.as_bytes()),
+                    Some(expected),
+                    "{input}"
+                );
+                assert_eq!(parse_temp_slow(input.as_bytes()), Some(expected), "{input}");
+                assert_eq!(parse_temp(input.as_bytes()), expected, "{input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_temp_checked_rejects() {
+        let cases: &[&[u8]] = &[
+            b"",
+            b"1",
+            b"1.",
+            b".1",
+            b"-",
+            b"-.1",
+            b"-1.",
+            b"100.0",
+            b"-100.0",
+            b"1.25",
+            b"12.",
+            b"abc",
+            b"--1.0",
+            b"+1.0",
+            b"1,0",
+            b"1..0",
+            b" 1.0",
+            b"1.0 ",
+            b"1.a",
+            b"a.1",
+            b"/.0",
+            b":.0",
+            b"1.\n",
+            b"\x001.0",
+            b"1\x00.0",
+            b"1.0\x00",
+            b"12345678.9",
+        ];
+
+        for &bytes in cases {
+            assert_eq!(
+                parse_temp_checked(bytes),
+                None,
+                "{:?}",
+                bytes.escape_ascii()
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_temp_format_matches_slow_path() {
+        // Digits, the characters either side of them, and the only other valid bytes.
+        let alphabet = b"/09:-.\x00";
+        let mut bytes = Vec::new();
+
+        for len in 0..=6 {
+            for i in 0..alphabet.len().pow(len) {
+                bytes.clear();
+                let mut rest = i;
+                for _ in 0..len {
+                    bytes.push(alphabet[rest % alphabet.len()]);
+                    rest /= alphabet.len();
+                }
+
+                let mut padded = [0u8; 8];
+                padded[..bytes.len()].copy_from_slice(&bytes);
+                let n = u64::from_le_bytes(padded);
+
+                let slow = parse_temp_slow(&bytes);
+                assert_eq!(
+                    is_temp_format(n, bytes.len()),
+                    slow.is_some(),
+                    "{:?}",
+                    bytes.escape_ascii()
+                );
+                assert_eq!(parse_temp_checked(&bytes), slow);
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregate_at_end_of_mapping() {
+        let rows = [
+            "Ab;1.0\n",
+            "Xi;-12.3\n",
+            "Cardinal;4.5\n",
+            "San Francisco;-99.9\n",
+        ];
+
+        // Every row count, so the final rows land on every offset within the last windows.
+        for n in 0..80 {
+            let input: String = (0..n).map(|i| rows[i * 7 % rows.len()]).collect();
+
+            let guarded = Guarded::new(input.as_bytes());
+            assert_matches_reference_all(guarded.as_slice(), &input, &Config::default());
+
+            let guarded = Guarded::new(input.trim_end().as_bytes());
+            assert_matches_reference_all(guarded.as_slice(), &input, &Config::default());
+        }
+    }
+
+    #[test]
+    fn test_aggregate_without_trailing_newline() {
+        let input = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nHamburg;-3.4\nSt. John's;15.2";
+        assert_matches_reference_all(input.as_bytes(), input, &Config::default());
+
+        let long = format!(
+            "{input}\n{};-1.5",
+            "Llanfairpwllgwyngyllgogerychwyrndrobwll".repeat(3)
+        );
+        assert_matches_reference_all(long.as_bytes(), &long, &Config::default());
+    }
+
+    #[test]
+    fn test_aggregate_empty() {
+        assert_matches_reference_all(b"", "", &Config::default());
+        assert_matches_reference_all(b"\n", "", &Config::default());
+    }
+
+    #[test]
+    fn test_aggregate_single_row() {
+        for input in ["Ab;1.0", "Ab;1.0\n", "Hamburg;-12.3", "Hamburg;-12.3\n"] {
+            assert_matches_reference_all(input.as_bytes(), input, &Config::default());
+        }
+    }
+
+    #[test]
+    fn test_split_regions() {
+        let buf = b"Ab;1.0\nCd;2.0\nEf;3.0\nGh;4.0\nIj;5.0";
+
+        let rows: Vec<&[u8]> = buf.split(|&b| b == b'\n').collect();
+
+        for n in 1..=8 {
+            let regions = split_regions(buf, n, b'\n');
+            assert_eq!(regions.len(), n);
+
+            let region_rows: Vec<&[u8]> = regions
+                .iter()
+                .filter(|region| !region.is_empty())
+                .flat_map(|region| region.split(|&b| b == b'\n'))
+                .collect();
+            assert_eq!(region_rows, rows, "n = {n}");
+        }
+
+        let regions = split_regions(buf, 3, b'\n');
+        assert!(regions.iter().all(|region| !region.is_empty()));
+    }
+
+    #[test]
+    fn test_split_regions_small() {
+        let buf = b"Ab;1.0\nCd;2.0";
+        let regions = split_regions(buf, 3, b'\n');
+
+        assert_eq!(regions, [&b""[..], b"Ab;1.0", b"Cd;2.0"]);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let input = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nHamburg;-3.4\nBulawayo;-10.1\nSt. John's;15.2\nHamburg;34.2\n";
+        assert_matches_reference(input);
+    }
+
+    #[test]
+    fn test_aggregate_long_names() {
+        assert!(LONG_NAME.len() > 64);
+
+        let input: String = (0..200)
+            .map(|i| match i % 3 {
+                0 => format!("{LONG_NAME};{}.{}\n", i % 50, i % 10),
+                1 => format!("Ur;-{}.{}\n", i % 30, i % 10),
+                _ => format!("Cardinal;{}.{}\n", i % 99, i % 10),
+            })
+            .collect();
+
+        assert_matches_reference(&input);
+    }
+
+    #[test]
+    fn test_aggregate_names_longer_than_128_bytes() {
+        let base = "Sensor/Region North/Site 12/Building C/Floor 3/Room 301/Rack 7/Unit 4/Probe";
+
+        let input: String = (0..300)
+            .map(|i| format!("{base}/{};{}.{}\n", "x".repeat(i % 7 * 40), i % 60, i % 10))
+            .collect();
+
+        assert!(input.lines().any(|line| line.len() > 256));
+        assert_matches_reference(&input);
+    }
+
+    #[test]
+    fn test_aggregate_many_rows() {
+        let names = [
+            "Abha",
+            "Baku",
+            "Cape Town",
+            "Dili",
+            "Edinburgh",
+            "Fianarantsoa",
+            "Greater Manchester",
+            "Hong Kong",
+            "Ouagadougou",
+            "San Francisco",
+        ];
+
+        let input: String = (0..10_000u32)
+            .map(|i| {
+                let name = names[(i.wrapping_mul(2654435761) % names.len() as u32) as usize];
+                let temp = (i.wrapping_mul(40503) % 1999) as i32 - 999;
+                let sign = if temp < 0 { "-" } else { "" };
+                format!("{name};{sign}{}.{}\n", temp.abs() / 10, temp.abs() % 10)
+            })
+            .collect();
+
+        assert_matches_reference(&input);
+    }
+
+    #[test]
+    fn test_aggregate_colliding_names() {
+        let input = "ABCDEFGHEFGHWXYZ;1.0\nABCDEFGHWXYZ;2.0\nABCDEFGHBCDEFGHX;3.0\nABCDEFGHX;4.0\nABCDEFGHWXYZ;-5.0\n";
+        assert_matches_reference(input);
+    }
+
+    #[test]
+    fn test_aggregate_interleaved_new_names() {
+        let (hash1, _) = Table::hash(b"Galatina");
+        let (hash2, _) = Table::hash(b"Zheleznogorsk");
+        assert_eq!(
+            hash1 & (TABLE_SIZE as u64 - 1),
+            hash2 & (TABLE_SIZE as u64 - 1)
+        );
+
+        // Regions a and b both start with a new name, and both names share a home slot.
+        let input = [
+            "Galatina;24.7\n".repeat(9),
+            "Zheleznogorsk;6.9\n".repeat(7),
+            "Galatina;24.7\n".repeat(8),
+            "Galatina;-24.7\n".to_string(),
+        ]
+        .concat();
+
+        let regions = split_regions(&input.as_bytes()[..input.len() - 1], 3, b'\n');
+        assert!(regions[0].starts_with(b"Galatina;"));
+        assert!(regions[1].starts_with(b"Zheleznogorsk;"));
+
+        assert_matches_reference(&input);
+    }
+
+    #[test]
+    fn test_aggregate_threads() {
+        let names = [
+            "Abha",
+            "Baku",
+            "Cape Town",
+            "Dili",
+            "Edinburgh",
+            "Hong Kong",
+        ];
+
+        let input: String = (0..5_000u32)
+            .map(|i| {
+                let name = names[(i.wrapping_mul(2654435761) % names.len() as u32) as usize];
+                format!("{name};{}.{}\n", i % 80, i % 10)
+            })
+            .collect();
+
+        for threads in [1, 2, 3, 8, 64] {
+            let config = Config {
+                threads,
+                ..Config::default()
+            };
+            let results = aggregate_with(input.as_bytes(), &config).unwrap();
+
+            assert_eq!(collect(&results), reference(&input), "threads = {threads}");
+        }
+    }
+
+    #[test]
+    fn test_aggregate_backends() {
+        let input: String = (0..3_000u32)
+            .map(|i| match i % 4 {
+                0 => format!("{LONG_NAME};{}.{}\n", i % 50, i % 10),
+                1 => format!("Station {};-{}.{}\n", i % 97, i % 30, i % 10),
+                2 => format!("Ur;{}.{}\n", i % 99, i % 10),
+                _ => format!("ABCDEFGHWXYZ;{}.{}\n", i % 40, i % 10),
+            })
+            .collect();
+
+        assert!(Backend::detect().is_supported());
+        assert_matches_reference_all(input.as_bytes(), &input, &Config::default());
+    }
+
+    #[test]
+    fn test_aggregate_many_names() {
+        let names = 100_000;
+        assert!(names > TABLE_SIZE);
+
+        let input: String = (0..names * 2)
+            .map(|i| format!("Station {};{}.{}\n", i % names, i % 90, i % 10))
+            .collect();
+
+        assert_matches_reference(&input);
+
+        let config = Config {
+            threads: 4,
+            ..Config::default()
+        };
+        let results = aggregate_with(input.as_bytes(), &config).unwrap();
+        assert_eq!(results.entries().len(), names);
+    }
+
+    #[test]
+    fn test_aggregate_beyond_u16_count() {
+        let input = "Hamburg;12.3\nBulawayo;-4.5\n".repeat(70_000);
+
+        let results = aggregate(input.as_bytes()).unwrap();
+        let entries = results.entries();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, b"Bulawayo");
+        assert_eq!(entries[0].1.count, 70_000);
+        assert_eq!(entries[0].1.sum, -45 * 70_000);
+        assert_eq!(entries[1].0, b"Hamburg");
+        assert_eq!(entries[1].1.count, 70_000);
+        assert_eq!(entries[1].1.sum, 123 * 70_000);
+    }
+}
@@ -0,0 +1,496 @@
+use super::{Precision, Results, Schema, TABLE_SIZE, Tables};
+use crate::error::{Error, SnapshotError};
+use crate::hash_table::{Entry, Histogram, Table, Temperature, Tracking};
+
+/// The first bytes of every snapshot.
+pub const MAGIC: [u8; 8] = *b"1BRCSNAP";
+
+/// The format version `encode` writes, and the only one `decode` reads.
+pub const VERSION: u32 = 1;
+
+/// Encodes the aggregated state of `results`, so it can be kept and merged with other results
+/// later without reading their input again. Quarantined rows are left out, but counted in the
+/// skipped rows.
+///
+/// All integers are little-endian:
+///
+/// - `MAGIC`, then `VERSION` as a `u32`.
+/// - The precision's decimals and integer digits as `u32`s, the key separator as a `u8` and the
+///   number of key fields as a `u32`, the number of value columns as a `u32`, the tracking as a
+///   `u8` (bit 0 for histograms, bit 1 for squares), then the skipped rows and the number of
+///   stations as `u64`s.
+/// - Each station, sorted by name: the name's length as a `u32` and the name, then each column's
+///   sum as an `i64`, count as a `u64`, and min and max as `i32`s. If tracked, each column's
+///   histogram follows as its lowest temperature as an `i32`, its number of buckets as a `u32` and
+///   each bucket's count as an unsigned LEB128 varint, then each column's sum of squares as a
+///   `u128`.
+/// - A 64-bit FNV-1a checksum of everything before it.
+pub fn encode(results: &Results) -> Vec<u8> {
+    let (separator, key_fields) = results.key_fields;
+    let tracking = results.tracking();
+    let entries = results.entries_columns();
+    let histograms = results.histograms();
+    let squares = results.squares();
+
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.extend(results.precision.decimals.to_le_bytes());
+    out.extend(results.precision.int_digits.to_le_bytes());
+    out.push(separator);
+    out.extend((key_fields as u32).to_le_bytes());
+    out.extend((results.columns() as u32).to_le_bytes());
+    out.push(tracking.histograms as u8 | (tracking.squares as u8) << 1);
+    out.extend(results.skipped.to_le_bytes());
+    out.extend((entries.len() as u64).to_le_bytes());
+
+    for (i, (name, columns)) in entries.iter().enumerate() {
+        out.extend((name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name);
+
+        for entry in columns.iter() {
+            out.extend(entry.sum.to_le_bytes());
+            out.extend(entry.count.to_le_bytes());
+            out.extend(entry.min.to_le_bytes());
+            out.extend(entry.max.to_le_bytes());
+        }
+
+        // Sorted by name like `entries`, if tracked.
+        for histogram in histograms.get(i).map_or(&[][..], |&(_, h)| h) {
+            let (lo, counts) = histogram.buckets();
+            out.extend(lo.to_le_bytes());
+            out.extend((counts.len() as u32).to_le_bytes());
+            for &count in counts {
+                push_varint(&mut out, count);
+            }
+        }
+
+        for squares in squares.get(i).map_or(&[][..], |&(_, s)| s) {
+            out.extend(squares.to_le_bytes());
+        }
+    }
+
+    out.extend(checksum(&out).to_le_bytes());
+    out
+}
+
+/// Decodes a snapshot written by `encode`. The results have no quarantined rows.
+pub fn decode(bytes: &[u8]) -> Result<Results, Error> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err(Error::Snapshot(SnapshotError::NotASnapshot));
+    }
+
+    let mut reader = Reader(&bytes[MAGIC.len()..]);
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(Error::Snapshot(SnapshotError::UnsupportedVersion(version)));
+    }
+
+    let Some((body, sum)) = reader.0.split_last_chunk() else {
+        return Err(Error::Snapshot(SnapshotError::Truncated));
+    };
+    if checksum(&bytes[..bytes.len() - sum.len()]) != u64::from_le_bytes(*sum) {
+        return Err(Error::Snapshot(SnapshotError::BadChecksum));
+    }
+
+    let mut reader = Reader(body);
+    let precision = Precision {
+        decimals: reader.u32()?,
+        int_digits: reader.u32()?,
+    };
+    let separator = reader.u8()?;
+    let key_fields = reader.u32()? as usize;
+    let columns = reader.u32()? as usize;
+    let tracked = reader.u8()?;
+    let tracking = Tracking {
+        histograms: tracked & 1 != 0,
+        squares: tracked & 2 != 0,
+    };
+    let skipped = reader.u64()?;
+    let stations = reader.u64()?;
+
+    if !precision.is_supported()
+        || !(1..=Schema::MAX_KEYS).contains(&key_fields)
+        || !(1..=Schema::MAX_VALUES).contains(&columns)
+        || tracked > 0b11
+        || (tracking.histograms && !precision.fits_i16())
+    {
+        return Err(Error::Snapshot(SnapshotError::Invalid));
+    }
+
+    let table = if precision.fits_i16() {
+        Tables::Narrow(decode_table(&mut reader, stations, columns, tracking)?)
+    } else {
+        Tables::Wide(decode_table(&mut reader, stations, columns, tracking)?)
+    };
+
+    if !reader.0.is_empty() {
+        return Err(Error::Snapshot(SnapshotError::Invalid));
+    }
+
+    Ok(Results {
+        table,
+        precision,
+        key_fields: (separator, key_fields),
+        skipped,
+        quarantined: Vec::new(),
+    })
+}
+
+/// Reads `stations` stations into a table, checking each one's stats are consistent.
+fn decode_table<T: Temperature>(
+    reader: &mut Reader,
+    stations: u64,
+    columns: usize,
+    tracking: Tracking,
+) -> Result<Table<T>, Error> {
+    let invalid = Error::Snapshot(SnapshotError::Invalid);
+    let narrow = |value: i32| {
+        let temp = T::from_i32(value);
+        match temp.into() == value as i64 {
+            true => Ok(temp),
+            false => Err(Error::Snapshot(SnapshotError::Invalid)),
+        }
+    };
+
+    let mut table = Table::with_tracking(TABLE_SIZE, columns, tracking);
+    let mut entries = Vec::with_capacity(columns);
+    let mut histograms = Vec::new();
+    let mut squares = Vec::new();
+
+    for _ in 0..stations {
+        let len = reader.u32()? as usize;
+        let name = reader.take(len)?;
+
+        entries.clear();
+        for _ in 0..columns {
+            let mut entry = Entry::default();
+            entry.sum = reader.i64()?;
+            entry.count = reader.u64()?;
+            entry.min = narrow(reader.i32()?)?;
+            entry.max = narrow(reader.i32()?)?;
+
+            let (min, max) = (entry.min.into() as i128, entry.max.into() as i128);
+            let (sum, count) = (entry.sum as i128, entry.count as i128);
+            if count == 0 || sum < min * count || sum > max * count {
+                return Err(invalid);
+            }
+            entries.push(entry);
+        }
+
+        histograms.clear();
+        for entry in entries.iter().filter(|_| tracking.histograms) {
+            // Histograms span exactly the temperatures seen.
+            let (min, max) = (entry.min.into(), entry.max.into());
+            let lo = reader.i32()?;
+            let buckets = reader.u32()? as i64;
+            if lo as i64 != min || buckets != max - min + 1 {
+                return Err(invalid);
+            }
+
+            let counts = (0..buckets)
+                .map(|_| reader.varint())
+                .collect::<Result<Vec<_>, _>>()?;
+            let total = counts.iter().try_fold(0u64, |sum, &c| sum.checked_add(c));
+            if total != Some(entry.count) {
+                return Err(invalid);
+            }
+            histograms.push(Histogram::from_buckets(lo, counts));
+        }
+
+        squares.clear();
+        for _ in entries.iter().filter(|_| tracking.squares) {
+            squares.push(reader.u128()?);
+        }
+
+        if name.is_empty() {
+            return Err(invalid);
+        }
+        table.insert(name, &entries, &histograms, &squares);
+    }
+
+    Ok(table)
+}
+
+/// Pushes `value` as an unsigned LEB128 varint: 7 bits per byte, low bits first, with the top bit
+/// set on all but the last byte.
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// 64-bit FNV-1a.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Reads little-endian integers off the front of a snapshot.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.0.len() {
+            return Err(Error::Snapshot(SnapshotError::Truncated));
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn u128(&mut self) -> Result<u128, Error> {
+        self.array().map(u128::from_le_bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::Snapshot(SnapshotError::Invalid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{self, Config, Policy};
+    use crate::output::{self, Format};
+
+    fn tracked() -> Config {
+        Config {
+            schema: Schema {
+                columns: 4,
+                keys: 1 << 0 | 1 << 1,
+                values: 1 << 2 | 1 << 3,
+            },
+            tracking: Tracking {
+                histograms: true,
+                squares: true,
+            },
+            ..Config::default()
+        }
+    }
+
+    fn rows(range: std::ops::Range<usize>) -> String {
+        range
+            .map(|i| {
+                let station = format!("Station {}", i % 37);
+                format!(
+                    "{station};{};{}.{};-{}.{}\n",
+                    i % 3,
+                    i % 99,
+                    i % 10,
+                    i % 7,
+                    i % 9
+                )
+            })
+            .collect()
+    }
+
+    /// Everything the output formatters show, which covers every stat.
+    fn rendered(results: &Results) -> Vec<u8> {
+        output::render(results, Format::Json.formatter())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let skip = Config {
+            policy: Policy::Skip,
+            ..Config::default()
+        };
+        let wide = Config {
+            precision: Precision {
+                decimals: 3,
+                int_digits: 3,
+            },
+            tracking: Tracking {
+                histograms: false,
+                squares: true,
+            },
+            ..Config::default()
+        };
+        let cases = [
+            (
+                skip,
+                "Hamburg;12.0\nBulawayo;8.9\nbad\nHamburg;-3.4\n".to_string(),
+            ),
+            (tracked(), rows(0..5_000)),
+            (
+                wide,
+                "Hamburg;123.456\nHamburg;-0.001\nZürich;-999.999\n".to_string(),
+            ),
+        ];
+
+        for (config, input) in cases {
+            let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+            let decoded = decode(&encode(&results)).unwrap();
+
+            assert_eq!(decoded.entries_columns(), results.entries_columns());
+            assert_eq!(decoded.histograms(), results.histograms());
+            assert_eq!(decoded.squares(), results.squares());
+            assert_eq!(decoded.skipped(), results.skipped());
+            assert_eq!(rendered(&decoded), rendered(&results));
+            assert_eq!(encode(&decoded), encode(&results));
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let config = tracked();
+        let aggregate = |input: &str| engine::aggregate_with(input.as_bytes(), &config).unwrap();
+
+        // Partly overlapping stations, so some are combined and some only seen once.
+        let mut merged = decode(&encode(&aggregate(&rows(0..3_000)))).unwrap();
+        let other = decode(&encode(&aggregate(&rows(2_990..4_000)))).unwrap();
+        merged.merge(other).unwrap();
+
+        let mut input = rows(0..3_000);
+        input.push_str(&rows(2_990..4_000));
+        assert_eq!(rendered(&merged), rendered(&aggregate(&input)));
+
+        let untracked = engine::aggregate(b"Hamburg;12.0\n").unwrap();
+        assert!(matches!(merged.merge(untracked), Err(Error::Incompatible)));
+    }
+
+    #[test]
+    fn test_layout() {
+        let config = Config {
+            tracking: Tracking {
+                histograms: true,
+                squares: true,
+            },
+            ..Config::default()
+        };
+        let input = "Ab;1.0\n".repeat(200) + "Ab;1.2\n";
+        let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+
+        let mut expected = MAGIC.to_vec();
+        expected.extend(1u32.to_le_bytes()); // version
+        expected.extend(1u32.to_le_bytes()); // decimals
+        expected.extend(2u32.to_le_bytes()); // integer digits
+        expected.push(b';'); // key separator
+        expected.extend(1u32.to_le_bytes()); // key fields
+        expected.extend(1u32.to_le_bytes()); // value columns
+        expected.push(0b11); // histograms and squares
+        expected.extend(0u64.to_le_bytes()); // skipped rows
+        expected.extend(1u64.to_le_bytes()); // stations
+        expected.extend(2u32.to_le_bytes());
+        expected.extend(b"Ab");
+        expected.extend(2012i64.to_le_bytes()); // sum
+        expected.extend(201u64.to_le_bytes()); // count
+        expected.extend(10i32.to_le_bytes()); // min
+        expected.extend(12i32.to_le_bytes()); // max
+        expected.extend(10i32.to_le_bytes()); // lowest bucket
+        expected.extend(3u32.to_le_bytes()); // buckets
+        expected.extend([0xc8, 0x01, 0x00, 0x01]); // 200, 0 and 1 as varints
+        expected.extend(20_144u128.to_le_bytes()); // sum of squares
+        expected.extend(checksum(&expected).to_le_bytes());
+
+        assert_eq!(encode(&results), expected);
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX - 1, u64::MAX] {
+            let mut out = Vec::new();
+            push_varint(&mut out, value);
+            assert_eq!(
+                out.len(),
+                (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
+            );
+
+            let mut reader = Reader(&out);
+            assert_eq!(reader.varint().unwrap(), value);
+            assert!(reader.0.is_empty());
+        }
+
+        let mut reader = Reader(&[0xff; 11]);
+        assert!(matches!(
+            reader.varint(),
+            Err(Error::Snapshot(SnapshotError::Invalid))
+        ));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let results = engine::aggregate_with(rows(0..100).as_bytes(), &tracked()).unwrap();
+        let bytes = encode(&results);
+        let error = |bytes: &[u8]| match decode(bytes) {
+            Err(Error::Snapshot(err)) => err,
+            other => panic!("expected a snapshot error, got {:?}", other.map(|_| ())),
+        };
+        // Recomputes the checksum after an edit.
+        let resealed = |mut bytes: Vec<u8>| {
+            let len = bytes.len() - 8;
+            let sum = checksum(&bytes[..len]);
+            bytes[len..].copy_from_slice(&sum.to_le_bytes());
+            bytes
+        };
+
+        assert_eq!(error(b"Hamburg;12.0\n"), SnapshotError::NotASnapshot);
+        assert_eq!(error(&bytes[..MAGIC.len() + 6]), SnapshotError::Truncated);
+
+        let mut newer = bytes.clone();
+        newer[8] = 2;
+        assert_eq!(error(&newer), SnapshotError::UnsupportedVersion(2));
+
+        for i in [20, bytes.len() / 2, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 1;
+            assert_eq!(error(&corrupted), SnapshotError::BadChecksum);
+        }
+        assert_eq!(error(&bytes[..bytes.len() - 1]), SnapshotError::BadChecksum);
+
+        // The tracking byte, with an unknown bit.
+        let mut tracking = bytes.clone();
+        tracking[29] |= 1 << 2;
+        assert_eq!(error(&resealed(tracking)), SnapshotError::Invalid);
+
+        // The first station's first count, past its name `Station 0;0`.
+        let count = 46 + 4 + "Station 0;0".len() + 8;
+        let mut count_zero = bytes.clone();
+        count_zero[count..count + 8].fill(0);
+        assert_eq!(error(&resealed(count_zero)), SnapshotError::Invalid);
+
+        let mut trailing = bytes.clone();
+        trailing.insert(bytes.len() - 8, 0);
+        assert_eq!(error(&resealed(trailing)), SnapshotError::Invalid);
+
+        assert!(decode(&resealed(bytes)).is_ok());
+    }
+}
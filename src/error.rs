@@ -0,0 +1,259 @@
+use crate::byte_buffer::Delimiters;
+use crate::engine::{Backend, Precision};
+use std::{fmt, io};
+
+/// A malformed input row, or a failure to read the input.
+///
+/// Row errors carry the byte offset of the start of the row, and its 1-based line number.
+#[derive(Debug)]
+pub enum Error {
+    MissingSeparator {
+        offset: usize,
+        line: usize,
+    },
+    BadTemperature {
+        offset: usize,
+        line: usize,
+    },
+    EmptyName {
+        offset: usize,
+        line: usize,
+    },
+    NameTooLong {
+        offset: usize,
+        line: usize,
+    },
+    InvalidUtf8 {
+        offset: usize,
+        line: usize,
+    },
+    Io(io::Error),
+    /// A `Config` that `engine::aggregate_with` can't aggregate with.
+    Config(ConfigError),
+    /// A snapshot that can't be decoded by `engine::snapshot::decode`.
+    Snapshot(SnapshotError),
+    /// Results that can't be merged, as their precision, keys, columns or tracking differ.
+    Incompatible,
+    /// A failure to export results with `output::arrow`.
+    #[cfg(feature = "arrow")]
+    Arrow(arrow_schema::ArrowError),
+    /// A failure to export results with `output::parquet`.
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+}
+
+/// What is wrong with a `Config`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The backend needs instructions the running CPU lacks.
+    UnsupportedBackend(Backend),
+    UnsupportedPrecision(Precision),
+    UnsupportedDelimiters(Delimiters),
+    /// Key and value columns that overlap, lie past the row's columns, or number too few or too
+    /// many.
+    UnsupportedSchema,
+    /// Histograms with a precision of more than 4 digits.
+    HistogramsTooPrecise(Precision),
+}
+
+/// What is wrong with a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The magic header is missing.
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    /// The contents don't match their checksum: they were corrupted or cut short.
+    BadChecksum,
+    Truncated,
+    /// The contents match their checksum, but aren't a valid aggregate.
+    Invalid,
+}
+
+/// What is wrong with a row, before its line number is known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RowError {
+    MissingSeparator,
+    BadTemperature,
+    EmptyName,
+    NameTooLong,
+    InvalidUtf8,
+}
+
+impl Error {
+    pub(crate) fn row(kind: RowError, offset: usize, line: usize) -> Self {
+        match kind {
+            RowError::MissingSeparator => Error::MissingSeparator { offset, line },
+            RowError::BadTemperature => Error::BadTemperature { offset, line },
+            RowError::EmptyName => Error::EmptyName { offset, line },
+            RowError::NameTooLong => Error::NameTooLong { offset, line },
+            RowError::InvalidUtf8 => Error::InvalidUtf8 { offset, line },
+        }
+    }
+
+    /// Byte offset of the malformed row, if this is a row error.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Error::MissingSeparator { offset, .. }
+            | Error::BadTemperature { offset, .. }
+            | Error::EmptyName { offset, .. }
+            | Error::NameTooLong { offset, .. }
+            | Error::InvalidUtf8 { offset, .. } => Some(offset),
+            Error::Io(_) | Error::Config(_) | Error::Snapshot(_) | Error::Incompatible => None,
+            #[cfg(feature = "arrow")]
+            Error::Arrow(_) => None,
+            #[cfg(feature = "parquet")]
+            Error::Parquet(_) => None,
+        }
+    }
+
+    /// Line number of the malformed row, if this is a row error.
+    pub fn line(&self) -> Option<usize> {
+        match *self {
+            Error::MissingSeparator { line, .. }
+            | Error::BadTemperature { line, .. }
+            | Error::EmptyName { line, .. }
+            | Error::NameTooLong { line, .. }
+            | Error::InvalidUtf8 { line, .. } => Some(line),
+            Error::Io(_) | Error::Config(_) | Error::Snapshot(_) | Error::Incompatible => None,
+            #[cfg(feature = "arrow")]
+            Error::Arrow(_) => None,
+            #[cfg(feature = "parquet")]
+            Error::Parquet(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingSeparator { offset, line } => {
+                write!(f, "line {line} (byte {offset}): missing separator")
+            }
+            Error::BadTemperature { offset, line } => {
+                write!(f, "line {line} (byte {offset}): bad temperature")
+            }
+            Error::EmptyName { offset, line } => {
+                write!(f, "line {line} (byte {offset}): station name is empty")
+            }
+            Error::NameTooLong { offset, line } => {
+                write!(f, "line {line} (byte {offset}): station name too long")
+            }
+            Error::InvalidUtf8 { offset, line } => {
+                write!(
+                    f,
+                    "line {line} (byte {offset}): station name is not valid UTF-8"
+                )
+            }
+            Error::Io(err) => write!(f, "{err}"),
+            Error::Config(ConfigError::UnsupportedBackend(backend)) => {
+                write!(f, "the {backend:?} backend is not supported on this CPU")
+            }
+            Error::Config(ConfigError::UnsupportedPrecision(precision)) => write!(
+                f,
+                "unsupported precision of {} integer digits and {} decimals: each needs at \
+                 least one, and at most 9 in all",
+                precision.int_digits, precision.decimals
+            ),
+            Error::Config(ConfigError::UnsupportedDelimiters(delims)) => write!(
+                f,
+                "unsupported separator '{}' and terminator '{}': they must differ, and neither \
+                 may be zero",
+                delims.separator.escape_ascii(),
+                delims.terminator.escape_ascii()
+            ),
+            Error::Config(ConfigError::UnsupportedSchema) => write!(
+                f,
+                "unsupported schema: key and value columns must not overlap or lie past the \
+                 last column, with 1 to 4 keys and 1 to 8 values"
+            ),
+            Error::Config(ConfigError::HistogramsTooPrecise(precision)) => write!(
+                f,
+                "percentiles need at most 4 digits in all, not {}",
+                precision.int_digits + precision.decimals
+            ),
+            Error::Snapshot(SnapshotError::NotASnapshot) => write!(f, "not a snapshot"),
+            Error::Snapshot(SnapshotError::UnsupportedVersion(version)) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            Error::Snapshot(SnapshotError::BadChecksum) => write!(f, "snapshot checksum mismatch"),
+            Error::Snapshot(SnapshotError::Truncated) => write!(f, "truncated snapshot"),
+            Error::Snapshot(SnapshotError::Invalid) => write!(f, "invalid snapshot"),
+            Error::Incompatible => {
+                write!(f, "results differ in precision, keys, columns or tracking")
+            }
+            #[cfg(feature = "arrow")]
+            Error::Arrow(err) => write!(f, "{err}"),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "arrow")]
+            Error::Arrow(err) => Some(err),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        Error::Arrow(err)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        Error::Parquet(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row() {
+        let err = Error::row(RowError::MissingSeparator, 7, 2);
+        assert!(matches!(
+            err,
+            Error::MissingSeparator { offset: 7, line: 2 }
+        ));
+        assert_eq!(err.to_string(), "line 2 (byte 7): missing separator");
+        assert_eq!((err.offset(), err.line()), (Some(7), Some(2)));
+
+        let err = Error::from(std::io::Error::other("disk on fire"));
+        assert_eq!(err.to_string(), "disk on fire");
+        assert_eq!((err.offset(), err.line()), (None, None));
+
+        let err = Error::Config(ConfigError::UnsupportedDelimiters(Delimiters {
+            separator: b'\t',
+            terminator: b'\t',
+            crlf: false,
+        }));
+        assert_eq!(
+            err.to_string(),
+            "unsupported separator '\\t' and terminator '\\t': they must differ, and neither may \
+             be zero"
+        );
+        assert_eq!((err.offset(), err.line()), (None, None));
+
+        let err = Error::Snapshot(SnapshotError::UnsupportedVersion(9));
+        assert_eq!(err.to_string(), "unsupported snapshot version 9");
+        assert_eq!((err.offset(), err.line()), (None, None));
+    }
+}
@@ -1,45 +1,231 @@
 use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
+use std::fmt::Debug;
 
 // (2^64) / \phi
 const MAGIC_CONST: i64 = 0x9E3779B97F4A7C15u64 as i64;
 
+/// Integer type temperatures are aggregated in, in units of the configured precision.
+pub trait Temperature: Copy + Ord + Default + Debug + Into<i64> + Send {
+    /// Narrows a parsed temperature, known to be in range.
+    fn from_i32(value: i32) -> Self;
+}
+
+impl Temperature for i16 {
+    #[inline(always)]
+    fn from_i32(value: i32) -> Self {
+        value as i16
+    }
+}
+
+impl Temperature for i32 {
+    #[inline(always)]
+    fn from_i32(value: i32) -> Self {
+        value
+    }
+}
+
+// Two entries per cache line with 16-bit temperatures, and one with 32-bit, so an entry never
+// straddles two lines.
+//
+// `sum` and `count` are 64-bit, so they can't overflow on billion-row inputs.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C, align(32))]
+pub struct Entry<T = i16> {
+    pub sum: i64,
+    pub count: u64,
+    pub min: T,
+    pub max: T,
+    /// Length of the station's name. Zero marks an empty slot, so names are never empty.
+    pub len: u32,
+    suffix: u64,
+}
+
+const _: () = assert!(std::mem::size_of::<Entry>() == 32);
+const _: () = assert!(std::mem::size_of::<Entry<i32>>() == 64);
+
+/// A name with its first value column's entry and the other columns', from `entries_columns`.
+type ColumnEntries<'a, T> = (&'a [u8], &'a Entry<T>, &'a [Entry<T>]);
+
+impl<T: Temperature> Entry<T> {
+    /// Adds a temperature to the stats, starting them if empty.
+    #[inline(always)]
+    fn record(&mut self, temp: T) {
+        if self.count == 0 {
+            self.min = temp;
+            self.max = temp;
+        }
+
+        self.sum += temp.into();
+        self.count += 1;
+        self.min = self.min.min(temp);
+        self.max = self.max.max(temp);
+    }
+
+    /// Adds another entry's stats to these.
+    #[inline(always)]
+    fn combine(&mut self, other: &Entry<T>) {
+        self.sum += other.sum;
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The mean temperature, rounded half up to a whole unit like the 1BRC reference's
+    /// `Math.round`. Exact, as it's computed from the integer sum and count.
+    ///
+    /// `count` must be non-zero, as it is for every entry in a table.
+    pub fn mean(&self) -> i64 {
+        debug_assert!(self.count != 0, "the mean of no temperatures is undefined");
+        let (sum, count) = (self.sum as i128, self.count as i128);
+        (2 * sum + count).div_euclid(2 * count) as i64
+    }
+
+    /// The population variance of the temperatures recorded, given their sum of squares, in units
+    /// of the precision squared. Computed from exact integers up to the final division, unless
+    /// `count * squares` overflows 128 bits, or `squares` isn't the temperatures' own.
+    pub fn variance(&self, squares: u128) -> f64 {
+        let count = self.count as u128;
+        let sum = self.sum.unsigned_abs() as u128;
+
+        // `sum^2` fits, as `|sum| <= 2^63`, and it's at most `count * squares` for the squares of
+        // the same temperatures. Any other squares fall back to floats rather than wrapping.
+        let deviations = count
+            .checked_mul(squares)
+            .and_then(|scaled| scaled.checked_sub(sum * sum));
+
+        match deviations {
+            Some(deviations) => deviations as f64 / (count as f64 * count as f64),
+            None => {
+                let mean = self.sum as f64 / self.count as f64;
+                (squares as f64 / self.count as f64 - mean * mean).max(0.0)
+            }
+        }
+    }
+
+    /// The same entry with 32-bit temperatures.
+    pub fn widen(&self) -> Entry<i32> {
+        Entry {
+            sum: self.sum,
+            count: self.count,
+            min: self.min.into() as i32,
+            max: self.max.into() as i32,
+            len: self.len,
+            suffix: self.suffix,
+        }
+    }
+}
+
+/// Exact counts of each temperature seen, in units of the configured precision. Only spans the
+/// range seen so far, so memory grows with the spread of temperatures rather than their bounds.
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
-#[repr(C)]
-pub struct Entry {
-    pub sum: i32,
-    pub count: u16,
-    pub min: i16,
-    pub max: i16,
-    pub len: u8,
-    _pad: [u8; 5],
+pub struct Histogram {
+    lo: i32,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    /// A histogram counting `counts[i]` temperatures of `lo + i`, as returned by `buckets`.
+    pub fn from_buckets(lo: i32, counts: Vec<u64>) -> Self {
+        Histogram { lo, counts }
+    }
+
+    /// The lowest temperature in range, and the count of each temperature from it up.
+    pub fn buckets(&self) -> (i32, &[u64]) {
+        (self.lo, &self.counts)
+    }
+
+    #[inline(always)]
+    fn record(&mut self, temp: i32) {
+        self.extend(temp, temp);
+        self.counts[(temp - self.lo) as usize] += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        if other.counts.is_empty() {
+            return;
+        }
+
+        self.extend(other.lo, other.lo + other.counts.len() as i32 - 1);
+        let offset = (other.lo - self.lo) as usize;
+        for (count, theirs) in self.counts[offset..].iter_mut().zip(&other.counts) {
+            *count += theirs;
+        }
+    }
+
+    /// Widens the range to cover `lo..=hi`.
+    #[inline(always)]
+    fn extend(&mut self, lo: i32, hi: i32) {
+        if self.counts.is_empty() {
+            self.lo = lo;
+            self.counts.resize((hi - lo) as usize + 1, 0);
+            return;
+        }
+
+        let hi = hi.max(self.lo + self.counts.len() as i32 - 1);
+        if lo < self.lo {
+            let mut counts = vec![0; (self.lo - lo) as usize];
+            counts.append(&mut self.counts);
+            self.counts = counts;
+            self.lo = lo;
+        }
+
+        self.counts.resize((hi - self.lo) as usize + 1, 0);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The nearest-rank `q`-quantile, for `q` in `0.0..=1.0`: the smallest temperature with at
+    /// least `q` of the counts at or below it. The median is the lower one for even counts.
+    /// `None` if empty.
+    pub fn quantile(&self, q: f64) -> Option<i32> {
+        let rank = ((q * self.count() as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(self.lo + i as i32);
+            }
+        }
+
+        None
+    }
+}
+
+/// Stats a table can keep per value column beyond `Entry`, updated by `Table::update_columns`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Tracking {
+    /// A `Histogram` of the temperatures, for percentiles.
+    pub histograms: bool,
+    /// The exact sum of the temperatures' squares, for `Entry::variance`.
+    pub squares: bool,
 }
 
-pub struct Table {
+pub struct Table<T = i16> {
     hash: Vec<u64>,
     prefix: Vec<u64>,
-    data: Vec<Entry>,
-    names: Vec<[u8; 128]>,
+    /// The first value column, along with the name's length and suffix.
+    data: Vec<Entry<T>>,
+    /// `columns - 1` entries per slot for the other value columns. Only their stats are used.
+    extra: Vec<Entry<T>>,
+    /// `columns` histograms per slot if tracked, otherwise empty.
+    histograms: Vec<Histogram>,
+    /// `columns` sums of squares per slot if tracked, otherwise empty.
+    squares: Vec<u128>,
+    offsets: Vec<usize>,
+    names: Vec<u8>,
+    columns: usize,
     size: usize,
+    len: usize,
 }
 
 impl Table {
-    pub fn new(size: usize) -> Self {
-        Self {
-            hash: vec![0u64; size],
-            prefix: vec![0u64; size],
-            data: vec![Entry::default(); size],
-            names: vec![[0u8; 128]; size],
-            size,
-        }
-    }
-
     pub fn hash(name: &[u8]) -> (u64, u64) {
         let len = name.len();
         let prefix = Table::prefix(name);
-        let suffix_offset = len.saturating_sub(8);
-        let suffix_mask = ((len > 8) as u64).wrapping_neg();
-        let suffix = unsafe { (name.as_ptr().add(suffix_offset) as *const u64).read_unaligned() }
-            & suffix_mask;
+        let suffix = Table::suffix(name);
 
         if len <= 16 {
             let hash = ((prefix ^ suffix) as i64).wrapping_mul(MAGIC_CONST);
@@ -60,12 +246,116 @@ impl Table {
         (hash as u64, prefix)
     }
 
+    /// The key for a tuple of fields: the fields joined by `separator`, which none of them may
+    /// contain, written over `key`. It's hashed and stored like any other name, so each field
+    /// can be split back out of it.
+    pub fn composite_key<'a, 'b>(
+        fields: impl IntoIterator<Item = &'b [u8]>,
+        separator: u8,
+        key: &'a mut Vec<u8>,
+    ) -> &'a [u8] {
+        key.clear();
+
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                key.push(separator);
+            }
+            key.extend_from_slice(field);
+        }
+
+        key
+    }
+
+    /// The first 8 bytes of `name`, zero-padded.
     #[inline(always)]
     pub fn prefix(name: &[u8]) -> u64 {
-        let len = name.len().min(8);
-        let mask = u64::MAX >> ((8 - len) * 8);
-        let bytes = unsafe { (name.as_ptr() as *const u64).read_unaligned() };
-        bytes & mask
+        let len = name.len();
+
+        if len >= 8 {
+            return unsafe { (name.as_ptr() as *const u64).read_unaligned() };
+        }
+
+        // Two overlapping in-bounds loads, so short names never read past their end.
+        if len >= 4 {
+            let lo = unsafe { (name.as_ptr() as *const u32).read_unaligned() } as u64;
+            let hi = unsafe { (name.as_ptr().add(len - 4) as *const u32).read_unaligned() } as u64;
+            return lo | (hi << ((len - 4) * 8));
+        }
+
+        if len > 0 {
+            let lo = name[0] as u64;
+            let mid = (name[len / 2] as u64) << (len / 2 * 8);
+            let hi = (name[len - 1] as u64) << ((len - 1) * 8);
+            return lo | mid | hi;
+        }
+
+        0
+    }
+
+    /// The last 8 bytes of names longer than 8 bytes, otherwise 0.
+    #[inline(always)]
+    pub fn suffix(name: &[u8]) -> u64 {
+        let len = name.len();
+
+        if len <= 8 {
+            return 0;
+        }
+
+        unsafe { (name.as_ptr().add(len - 8) as *const u64).read_unaligned() }
+    }
+}
+
+impl<T: Temperature> Table<T> {
+    pub fn new(size: usize) -> Self {
+        Table::with_columns(size, 1)
+    }
+
+    /// A table aggregating `columns` values per name, updated with `update_columns`.
+    pub fn with_columns(size: usize, columns: usize) -> Self {
+        assert!(size.is_power_of_two(), "table size must be a power of two");
+        assert!(columns >= 1, "tables need at least one value column");
+
+        Self {
+            hash: vec![0u64; size],
+            prefix: vec![0u64; size],
+            data: vec![Entry::default(); size],
+            extra: vec![Entry::default(); size * (columns - 1)],
+            histograms: Vec::new(),
+            squares: Vec::new(),
+            offsets: vec![0usize; size],
+            names: Vec::new(),
+            columns,
+            size,
+            len: 0,
+        }
+    }
+
+    /// Like `with_columns`, also keeping the stats in `tracking` per value column.
+    pub fn with_tracking(size: usize, columns: usize, tracking: Tracking) -> Self {
+        let mut table = Table::with_columns(size, columns);
+        if tracking.histograms {
+            table.histograms = vec![Histogram::default(); size * columns];
+        }
+        if tracking.squares {
+            table.squares = vec![0; size * columns];
+        }
+        table
+    }
+
+    /// An empty table of `size` slots, with the same columns and tracking as this one.
+    fn empty_like(&self, size: usize) -> Self {
+        Table::with_tracking(size, self.columns, self.tracking())
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn tracking(&self) -> Tracking {
+        Tracking {
+            histograms: !self.histograms.is_empty(),
+            squares: !self.squares.is_empty(),
+        }
     }
 
     #[inline(always)]
@@ -76,45 +366,65 @@ impl Table {
         }
     }
 
-    #[cfg(all(target_feature = "avx512f", target_feature = "avx512bw"))]
+    /// Picks the fastest implementation the CPU supports at runtime.
     #[inline(always)]
     pub fn lookup(&self, hash: u64, prefix: u64) -> usize {
+        if is_x86_feature_detected!("avx512f") {
+            return unsafe { self.lookup_avx512(hash, prefix) };
+        }
+
+        self.lookup_scalar(hash, prefix)
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F.
+    #[inline(always)]
+    pub unsafe fn lookup_avx512(&self, hash: u64, prefix: u64) -> usize {
         let size_mask = self.size - 1;
         let slot = hash as usize & size_mask;
 
         unsafe {
             #[cfg(target_arch = "x86")]
             use std::arch::x86::{
-                __m512i, _mm512_cmpeq_epi64_mask, _mm512_loadu_si512, _mm512_set1_epi64,
+                _mm512_cmpeq_epi64_mask, _mm512_maskz_loadu_epi64, _mm512_set1_epi64,
                 _mm512_setzero_si512,
             };
 
             #[cfg(target_arch = "x86_64")]
             use std::arch::x86_64::{
-                __m512i, _mm512_cmpeq_epi64_mask, _mm512_loadu_si512, _mm512_set1_epi64,
+                _mm512_cmpeq_epi64_mask, _mm512_maskz_loadu_epi64, _mm512_set1_epi64,
                 _mm512_setzero_si512,
             };
 
-            let h = _mm512_loadu_si512(self.hash[slot..].as_ptr() as *const __m512i);
+            // Lanes past the end of the table are masked off rather than read, and the
+            // remainder of the probe sequence wraps around in `probe`.
+            let lanes = (self.size - slot).min(8);
+            let valid = u8::MAX >> (8 - lanes);
+
+            let h = _mm512_maskz_loadu_epi64(valid, self.hash.as_ptr().add(slot) as *const i64);
             let target_h = _mm512_set1_epi64(hash as i64);
             let match_h = _mm512_cmpeq_epi64_mask(h, target_h);
 
-            let p = _mm512_loadu_si512(self.prefix[slot..].as_ptr() as *const __m512i);
+            let p = _mm512_maskz_loadu_epi64(valid, self.prefix.as_ptr().add(slot) as *const i64);
             let target_p = _mm512_set1_epi64(prefix as i64);
             let match_p = _mm512_cmpeq_epi64_mask(p, target_p);
 
             let zero = _mm512_setzero_si512();
             let empty = _mm512_cmpeq_epi64_mask(h, zero);
 
-            let matches = empty | (match_h & match_p);
+            let matches = (empty | (match_h & match_p)) & valid;
 
-            (slot + matches.trailing_zeros() as usize) & size_mask
+            if matches == 0 {
+                return self.probe(slot + lanes, hash, prefix);
+            }
+
+            slot + matches.trailing_zeros() as usize
         }
     }
 
-    #[cfg(not(any(target_feature = "avx512f", target_feature = "avx512bw")))]
     #[inline(always)]
-    pub fn lookup(&self, hash: u64, prefix: u64) -> usize {
+    pub fn lookup_scalar(&self, hash: u64, prefix: u64) -> usize {
         let size_mask = self.size - 1;
         let slot = hash as usize & size_mask;
 
@@ -137,42 +447,427 @@ impl Table {
         let m4 = ((h4 == 0) | ((h4 == hash) & (p4 == prefix))) as u32;
 
         let mask = m0 | (m1 << 1) | (m2 << 2) | (m3 << 3) | (m4 << 4);
+
+        if mask == 0 {
+            return self.probe(slot + 5, hash, prefix);
+        }
+
         let first = mask.trailing_zeros() as usize;
 
         (slot + first) & size_mask
     }
 
+    #[cold]
+    #[inline(never)]
+    fn probe(&self, slot: usize, hash: u64, prefix: u64) -> usize {
+        let size_mask = self.size - 1;
+        let mut slot = slot & size_mask;
+
+        loop {
+            let h = self.hash[slot];
+
+            if h == 0 || (h == hash && self.prefix[slot] == prefix) {
+                return slot;
+            }
+
+            slot = (slot + 1) & size_mask;
+        }
+    }
+
+    /// Like `lookup`, but also compares the full stored name on a hash and prefix match, so
+    /// distinct names that collide on both are never merged.
+    #[inline(always)]
+    pub fn lookup_exact(&self, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        let slot = self.lookup(hash, prefix);
+        self.resolve_exact(slot, hash, prefix, name)
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512F.
+    #[inline(always)]
+    pub unsafe fn lookup_exact_avx512(&self, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        let slot = unsafe { self.lookup_avx512(hash, prefix) };
+        self.resolve_exact(slot, hash, prefix, name)
+    }
+
+    #[inline(always)]
+    pub fn lookup_exact_scalar(&self, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        let slot = self.lookup_scalar(hash, prefix);
+        self.resolve_exact(slot, hash, prefix, name)
+    }
+
+    #[inline(always)]
+    fn resolve_exact(&self, slot: usize, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        if self.is_empty_or_match(slot, hash, prefix, name) {
+            return slot;
+        }
+
+        self.probe_exact(slot, hash, prefix, name)
+    }
+
+    #[cold]
     #[inline(never)]
-    pub fn update(&mut self, slot: usize, hash: u64, prefix: u64, name: &[u8], temp: i16) {
+    fn probe_exact(&self, slot: usize, hash: u64, prefix: u64, name: &[u8]) -> usize {
+        let size_mask = self.size - 1;
+        let mut slot = (slot + 1) & size_mask;
+
+        while !self.is_empty_or_match(slot, hash, prefix, name) {
+            slot = (slot + 1) & size_mask;
+        }
+
+        slot
+    }
+
+    #[inline(always)]
+    fn is_empty_or_match(&self, slot: usize, hash: u64, prefix: u64, name: &[u8]) -> bool {
+        let entry = unsafe { self.data.get_unchecked(slot) };
+        let len = entry.len as usize;
+
+        if len == 0 {
+            return true;
+        }
+
+        let &h = unsafe { self.hash.get_unchecked(slot) };
+        let &p = unsafe { self.prefix.get_unchecked(slot) };
+
+        // Prefix and suffix cover names of up to 16 bytes without touching `names`.
+        ((h == hash) & (p == prefix) & (len == name.len()) & (entry.suffix == Table::suffix(name)))
+            && (len <= 16 || self.name(slot)[8..len - 8] == name[8..len - 8])
+    }
+
+    /// Adds `temp` to the stats of `name`, in the `slot` a lookup returned for it. `name` must
+    /// not be empty: an empty name would leave its slot looking empty, and claim a new one on
+    /// every update.
+    #[inline(never)]
+    pub fn update(&mut self, slot: usize, hash: u64, prefix: u64, name: &[u8], temp: T) {
+        debug_assert!(!name.is_empty(), "names must not be empty");
         let len = name.len();
         let entry = unsafe { self.data.get_unchecked_mut(slot) };
 
         if entry.len != 0 {
-            entry.sum += temp as i32;
+            entry.sum += temp.into();
             entry.count += 1;
             entry.min = entry.min.min(temp);
             entry.max = entry.max.max(temp);
             return;
         }
 
-        entry.sum = temp as i32;
+        entry.sum = temp.into();
         entry.count = 1;
         entry.min = temp;
         entry.max = temp;
-        entry.len = len as u8;
+        entry.len = len as u32;
+        entry.suffix = Table::suffix(name);
 
         self.hash[slot] = hash;
         self.prefix[slot] = prefix;
-        self.names[slot][..len].copy_from_slice(name);
+        self.offsets[slot] = self.names.len();
+        self.names.extend_from_slice(name);
+
+        self.occupied();
     }
 
+    /// Like `update`, with one temperature per value column.
     #[inline(never)]
-    pub fn entries(&self) -> Vec<(&[u8; 128], &Entry)> {
+    pub fn update_columns(
+        &mut self,
+        slot: usize,
+        hash: u64,
+        prefix: u64,
+        name: &[u8],
+        temps: &[T],
+    ) {
+        assert_eq!(
+            temps.len(),
+            self.columns,
+            "expected one temperature per column"
+        );
+
+        // Before `update`, which may grow the table and move the slot.
+        for (entry, &temp) in self.extra_mut(slot).iter_mut().zip(&temps[1..]) {
+            entry.record(temp);
+        }
+
+        for (histogram, &temp) in self.histograms_mut(slot).iter_mut().zip(temps) {
+            histogram.record(temp.into() as i32);
+        }
+
+        for (squares, &temp) in self.squares_mut(slot).iter_mut().zip(temps) {
+            *squares += temp.into().unsigned_abs().pow(2) as u128;
+        }
+
+        self.update(slot, hash, prefix, name, temps[0]);
+    }
+
+    pub fn merge(&mut self, other: &Table<T>) {
+        assert_eq!(
+            self.columns, other.columns,
+            "tables must have the same columns"
+        );
+        assert_eq!(
+            self.tracking(),
+            other.tracking(),
+            "tables must have the same tracking"
+        );
+
+        for (i, theirs) in other.data.iter().enumerate() {
+            if theirs.len == 0 {
+                continue;
+            }
+
+            let hash = other.hash[i];
+            let prefix = other.prefix[i];
+            let name = other.name(i);
+            let slot = self.lookup_exact(hash, prefix, name);
+
+            if self.data[slot].len != 0 {
+                let (extra, histograms) = (other.extra(i), other.histograms(i));
+                self.combine(slot, theirs, extra, histograms, other.squares(i));
+                continue;
+            }
+
+            self.claim(slot, other, i);
+        }
+    }
+
+    /// Adds a name's stats, aggregated elsewhere, to the table: one entry per value column, and
+    /// one histogram and sum of squares per column if tracked, as `entries_columns`,
+    /// `entries_histograms` and `entries_squares` return them.
+    pub fn insert(
+        &mut self,
+        name: &[u8],
+        columns: &[Entry<T>],
+        histograms: &[Histogram],
+        squares: &[u128],
+    ) {
+        assert!(!name.is_empty(), "names must not be empty");
+        assert_eq!(columns.len(), self.columns, "expected one entry per column");
+        assert_eq!(
+            histograms.len(),
+            self.tracked_width(&self.histograms),
+            "expected one histogram per column if tracked"
+        );
+        assert_eq!(
+            squares.len(),
+            self.tracked_width(&self.squares),
+            "expected one sum of squares per column if tracked"
+        );
+
+        let (hash, prefix) = Table::hash(name);
+        let slot = self.lookup_exact(hash, prefix, name);
+
+        if self.data[slot].len != 0 {
+            self.combine(slot, &columns[0], &columns[1..], histograms, squares);
+            return;
+        }
+
+        self.data[slot] = columns[0].clone();
+        self.data[slot].len = name.len() as u32;
+        self.data[slot].suffix = Table::suffix(name);
+        self.extra_mut(slot).clone_from_slice(&columns[1..]);
+        self.histograms_mut(slot).clone_from_slice(histograms);
+        self.squares_mut(slot).copy_from_slice(squares);
+        self.hash[slot] = hash;
+        self.prefix[slot] = prefix;
+        self.offsets[slot] = self.names.len();
+        self.names.extend_from_slice(name);
+
+        self.occupied();
+    }
+
+    /// Adds stats to those of the occupied `slot`.
+    #[inline(always)]
+    fn combine(
+        &mut self,
+        slot: usize,
+        first: &Entry<T>,
+        extra: &[Entry<T>],
+        histograms: &[Histogram],
+        squares: &[u128],
+    ) {
+        self.data[slot].combine(first);
+        for (entry, theirs) in self.extra_mut(slot).iter_mut().zip(extra) {
+            entry.combine(theirs);
+        }
+        for (histogram, theirs) in self.histograms_mut(slot).iter_mut().zip(histograms) {
+            histogram.merge(theirs);
+        }
+        for (ours, theirs) in self.squares_mut(slot).iter_mut().zip(squares) {
+            *ours += theirs;
+        }
+    }
+
+    /// Drops every station `keep` returns false for. Rebuilds the table, so it's only meant for
+    /// rare cleanups after aggregation.
+    pub fn retain(&mut self, mut keep: impl FnMut(&[u8], &Entry<T>) -> bool) {
+        let mut kept = self.empty_like(self.size);
+
+        for (i, entry) in self.data.iter().enumerate() {
+            let name = self.name(i);
+            if entry.len == 0 || !keep(name, entry) {
+                continue;
+            }
+
+            let slot = kept.lookup_exact(self.hash[i], self.prefix[i], name);
+            kept.claim(slot, self, i);
+        }
+
+        *self = kept;
+    }
+
+    /// Fills the empty `slot` with a copy of slot `i` of `other`.
+    #[inline(always)]
+    fn claim(&mut self, slot: usize, other: &Table<T>, i: usize) {
+        self.data[slot] = other.data[i].clone();
+        self.extra_mut(slot).clone_from_slice(other.extra(i));
+        self.histograms_mut(slot)
+            .clone_from_slice(other.histograms(i));
+        self.squares_mut(slot).copy_from_slice(other.squares(i));
+        self.hash[slot] = other.hash[i];
+        self.prefix[slot] = other.prefix[i];
+        self.offsets[slot] = self.names.len();
+        self.names.extend_from_slice(other.name(i));
+
+        self.occupied();
+    }
+
+    #[inline(always)]
+    fn occupied(&mut self) {
+        self.len += 1;
+
+        // Keep probe sequences short, and guarantee they always reach an empty slot.
+        if self.len * 4 > self.size * 3 {
+            self.grow();
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn grow(&mut self) {
+        let mut grown = self.empty_like(self.size * 2);
+        let size_mask = grown.size - 1;
+
+        // Moved rather than copied, as they may be large.
+        let width = self.tracked_width(&self.histograms);
+        let mut histograms = std::mem::take(&mut self.histograms);
+
+        for (i, entry) in self.data.iter().enumerate() {
+            if entry.len == 0 {
+                continue;
+            }
+
+            let mut slot = self.hash[i] as usize & size_mask;
+            while grown.data[slot].len != 0 {
+                slot = (slot + 1) & size_mask;
+            }
+
+            grown.hash[slot] = self.hash[i];
+            grown.prefix[slot] = self.prefix[i];
+            grown.data[slot] = entry.clone();
+            grown.extra_mut(slot).clone_from_slice(self.extra(i));
+            for (histogram, ours) in grown
+                .histograms_mut(slot)
+                .iter_mut()
+                .zip(&mut histograms[i * width..])
+            {
+                *histogram = std::mem::take(ours);
+            }
+            grown.squares_mut(slot).copy_from_slice(self.squares(i));
+            grown.offsets[slot] = self.offsets[i];
+        }
+
+        grown.names = std::mem::take(&mut self.names);
+        grown.len = self.len;
+        *self = grown;
+    }
+
+    #[inline(always)]
+    fn extra(&self, slot: usize) -> &[Entry<T>] {
+        let width = self.columns - 1;
+        &self.extra[slot * width..(slot + 1) * width]
+    }
+
+    #[inline(always)]
+    fn extra_mut(&mut self, slot: usize) -> &mut [Entry<T>] {
+        let width = self.columns - 1;
+        &mut self.extra[slot * width..(slot + 1) * width]
+    }
+
+    /// Tracked stats per slot: one per column if tracked, otherwise none.
+    #[inline(always)]
+    fn tracked_width<S>(&self, tracked: &[S]) -> usize {
+        if tracked.is_empty() { 0 } else { self.columns }
+    }
+
+    #[inline(always)]
+    fn histograms(&self, slot: usize) -> &[Histogram] {
+        let width = self.tracked_width(&self.histograms);
+        &self.histograms[slot * width..(slot + 1) * width]
+    }
+
+    #[inline(always)]
+    fn histograms_mut(&mut self, slot: usize) -> &mut [Histogram] {
+        let width = self.tracked_width(&self.histograms);
+        &mut self.histograms[slot * width..(slot + 1) * width]
+    }
+
+    #[inline(always)]
+    fn squares(&self, slot: usize) -> &[u128] {
+        let width = self.tracked_width(&self.squares);
+        &self.squares[slot * width..(slot + 1) * width]
+    }
+
+    #[inline(always)]
+    fn squares_mut(&mut self, slot: usize) -> &mut [u128] {
+        let width = self.tracked_width(&self.squares);
+        &mut self.squares[slot * width..(slot + 1) * width]
+    }
+
+    #[inline(always)]
+    fn name(&self, slot: usize) -> &[u8] {
+        let offset = self.offsets[slot];
+        let len = self.data[slot].len as usize;
+        &self.names[offset..offset + len]
+    }
+
+    #[inline(never)]
+    pub fn entries(&self) -> Vec<(&[u8], &Entry<T>)> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.len != 0)
+            .map(|(i, m)| (self.name(i), m))
+            .collect()
+    }
+
+    /// Each name's histograms, one per value column, if tracked.
+    pub fn entries_histograms(&self) -> Vec<(&[u8], &[Histogram])> {
         self.data
             .iter()
             .enumerate()
             .filter(|(_, m)| m.len != 0)
-            .map(|(i, m)| (&self.names[i], m))
+            .map(|(i, _)| (self.name(i), self.histograms(i)))
+            .collect()
+    }
+
+    /// Each name's sums of squares, one per value column, if tracked.
+    pub fn entries_squares(&self) -> Vec<(&[u8], &[u128])> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.len != 0)
+            .map(|(i, _)| (self.name(i), self.squares(i)))
+            .collect()
+    }
+
+    /// Like `entries`, with the other value columns' entries, in column order.
+    pub fn entries_columns(&self) -> Vec<ColumnEntries<'_, T>> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.len != 0)
+            .map(|(i, m)| (self.name(i), m, self.extra(i)))
             .collect()
     }
 }
@@ -180,6 +875,7 @@ impl Table {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::Guarded;
 
     #[test]
     fn test_hash() {
@@ -200,9 +896,42 @@ mod tests {
         assert_ne!(b_hash, c_hash);
     }
 
+    #[test]
+    fn test_hash_at_end_of_mapping() {
+        let bytes = b"Llanfairpwllgwyngyllgogerychwyrndrobwllllantysiliogogogoch";
+
+        for len in 0..=bytes.len() {
+            let name = &bytes[bytes.len() - len..];
+            let guarded = Guarded::new(name);
+
+            assert_eq!(Table::hash(guarded.as_slice()), Table::hash(name));
+
+            let mut padded = name.to_vec();
+            padded.resize(len.max(8), 0);
+            assert_eq!(
+                Table::prefix(guarded.as_slice()),
+                u64::from_le_bytes(padded[..8].try_into().unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_composite_key() {
+        let mut key = b"stale".to_vec();
+
+        let fields: [&[u8]; 3] = [b"Hamburg", b"2024-01", b""];
+        assert_eq!(
+            Table::composite_key(fields, b';', &mut key),
+            b"Hamburg;2024-01;"
+        );
+        assert_eq!(Table::hash(&key), Table::hash(b"Hamburg;2024-01;"));
+
+        assert_eq!(Table::composite_key([&b"Ur"[..]], b';', &mut key), b"Ur");
+    }
+
     #[test]
     fn test_lookup() {
-        let tbl = Table::new(16);
+        let tbl: Table = Table::new(16);
 
         let key1 = "Cardinal".as_bytes();
         let key2 = "Wolsey".as_bytes();
@@ -232,4 +961,549 @@ mod tests {
         assert_eq!(tbl.data[slot1].sum, 300);
         assert_eq!(tbl.data[slot2].sum, 20);
     }
+
+    #[test]
+    fn test_lookup_exact_collisions() {
+        // Hashing folds the last 8 bytes into the prefix, so these share a prefix and a hash.
+        let pairs: &[(&[u8], &[u8])] = &[
+            (b"ABCDEFGHEFGHWXYZ", b"ABCDEFGHWXYZ"),
+            (b"A", b"A\0"),
+            (b"ABCDEFGHBCDEFGHX", b"ABCDEFGHX"),
+            (
+                b"ABCDEFGH11111111222222223333333X",
+                b"ABCDEFGH22222222111111113333333X",
+            ),
+        ];
+
+        for &(key1, key2) in pairs {
+            let (hash1, prefix1) = Table::hash(key1);
+            let (hash2, prefix2) = Table::hash(key2);
+            assert_eq!((hash1, prefix1), (hash2, prefix2));
+
+            let mut tbl = Table::new(16);
+
+            let slot1 = tbl.lookup_exact(hash1, prefix1, key1);
+            tbl.update(slot1, hash1, prefix1, key1, 100);
+
+            assert_eq!(tbl.lookup(hash2, prefix2), slot1);
+
+            let slot2 = tbl.lookup_exact(hash2, prefix2, key2);
+            assert_ne!(slot1, slot2);
+            tbl.update(slot2, hash2, prefix2, key2, -100);
+
+            assert_eq!(tbl.lookup_exact(hash1, prefix1, key1), slot1);
+            assert_eq!(tbl.lookup_exact(hash2, prefix2, key2), slot2);
+            assert_eq!(tbl.data[slot1].sum, 100);
+            assert_eq!(tbl.data[slot2].sum, -100);
+        }
+    }
+
+    #[test]
+    fn test_lookup_wraps_past_window() {
+        let size = 16;
+        let mut tbl = Table::new(size);
+
+        // Names that all want the last slot, so their probe sequence wraps and outgrows the
+        // lookup window.
+        let keys: Vec<Vec<u8>> = (0..)
+            .map(|i| format!("Station {i}").into_bytes())
+            .filter(|key| Table::hash(key).0 as usize & (size - 1) == size - 1)
+            .take(10)
+            .collect();
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup(hash, prefix);
+            assert_eq!(tbl.data[slot].len, 0);
+            tbl.update(slot, hash, prefix, key, i as i16);
+        }
+
+        assert_eq!(tbl.size, size);
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup(hash, prefix);
+            assert_eq!(slot, tbl.lookup_exact(hash, prefix, key));
+            assert_eq!(slot, (size - 1 + i) & (size - 1));
+            assert_eq!(tbl.name(slot), &key[..]);
+        }
+    }
+
+    #[test]
+    fn test_lookup_backends_agree() {
+        let size = 64;
+        let mut tbl = Table::new(size);
+
+        let keys: Vec<Vec<u8>> = (0..40)
+            .map(|i| format!("Station {i}").into_bytes())
+            .collect();
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact_scalar(hash, prefix, key);
+
+            if is_x86_feature_detected!("avx512f") {
+                assert_eq!(
+                    unsafe { tbl.lookup_avx512(hash, prefix) },
+                    tbl.lookup_scalar(hash, prefix)
+                );
+                assert_eq!(unsafe { tbl.lookup_exact_avx512(hash, prefix, key) }, slot);
+            }
+
+            tbl.update(slot, hash, prefix, key, i as i16);
+        }
+
+        for key in &keys {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact_scalar(hash, prefix, key);
+            assert_eq!(tbl.name(slot), &key[..]);
+
+            if is_x86_feature_detected!("avx512f") {
+                assert_eq!(
+                    unsafe { tbl.lookup_avx512(hash, prefix) },
+                    tbl.lookup_scalar(hash, prefix)
+                );
+                assert_eq!(unsafe { tbl.lookup_exact_avx512(hash, prefix, key) }, slot);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut tbl = Table::new(16);
+
+        let keys: Vec<Vec<u8>> = (0..10_000)
+            .map(|i| format!("Station {i}").into_bytes())
+            .collect();
+
+        for round in 0..2 {
+            for (i, key) in keys.iter().enumerate() {
+                let (hash, prefix) = Table::hash(key);
+                let slot = tbl.lookup_exact(hash, prefix, key);
+                tbl.update(slot, hash, prefix, key, (i % 100) as i16 + round);
+            }
+        }
+
+        assert_eq!(tbl.len, keys.len());
+        assert!(tbl.len * 4 <= tbl.size * 3);
+        assert_eq!(tbl.entries().len(), keys.len());
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact(hash, prefix, key);
+            let entry = &tbl.data[slot];
+
+            assert_eq!(tbl.name(slot), &key[..]);
+            assert_eq!(entry.count, 2);
+            assert_eq!(entry.min, (i % 100) as i16);
+            assert_eq!(entry.max, (i % 100) as i16 + 1);
+        }
+    }
+
+    #[test]
+    fn test_long_names() {
+        let mut tbl = Table::new(16);
+
+        let keys: Vec<Vec<u8>> = [8, 16, 127, 128, 129, 300, 5_000, 70_000]
+            .iter()
+            .map(|&len| (0..len).map(|i| b'A' + (i % 26) as u8).collect())
+            .collect();
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact(hash, prefix, key);
+            tbl.update(slot, hash, prefix, key, i as i16);
+        }
+
+        let mut entries = tbl.entries();
+        entries.sort_unstable_by_key(|&(name, _)| name.len());
+
+        assert_eq!(entries.len(), keys.len());
+
+        for (i, (name, entry)) in entries.iter().enumerate() {
+            assert_eq!(*name, &keys[i][..]);
+            assert_eq!(entry.len as usize, keys[i].len());
+            assert_eq!(entry.sum, i as i64);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Table::new(16);
+        let mut b = Table::new(16);
+
+        let key1 = "Cardinal".as_bytes();
+        let key2 = "Wolsey".as_bytes();
+        let key3 = "Greater Manchester".as_bytes();
+
+        let insert = |tbl: &mut Table, key: &[u8], temp: i16| {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup(hash, prefix);
+            tbl.update(slot, hash, prefix, key, temp);
+        };
+
+        insert(&mut a, key1, 300);
+        insert(&mut a, key2, -20);
+        insert(&mut b, key2, 45);
+        insert(&mut b, key2, -75);
+        insert(&mut b, key3, 12);
+
+        a.merge(&b);
+
+        let mut entries = a.entries();
+        entries.sort_unstable_by_key(|&(name, _)| name);
+
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|&(name, e)| (name, e.min, e.sum, e.count, e.max))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (key1, 300, 300, 1, 300),
+                (key3, 12, 12, 1, 12),
+                (key2, -75, -50, 3, 45),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_columns() {
+        let mut a = Table::with_columns(16, 3);
+        let mut b = Table::with_columns(16, 3);
+        let keys: Vec<String> = (0..40).map(|i| format!("Station {}", i % 20)).collect();
+
+        let insert = |tbl: &mut Table, key: &[u8], temps: &[i16]| {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact(hash, prefix, key);
+            tbl.update_columns(slot, hash, prefix, key, temps);
+        };
+
+        // Enough names to grow both tables, so extra columns are moved along with their slots.
+        for (i, key) in keys.iter().enumerate() {
+            let i = i as i16;
+            insert(&mut a, key.as_bytes(), &[i, -i, 100 + i]);
+            insert(&mut b, key.as_bytes(), &[2 * i, 0, -100]);
+        }
+        insert(&mut b, b"Elsewhere", &[1, 2, 3]);
+
+        a.merge(&b);
+        a.retain(|name, _| name != b"Station 0");
+
+        let mut entries = a.entries_columns();
+        entries.sort_unstable_by_key(|&(name, _, _)| name);
+        assert_eq!(entries.len(), 20);
+
+        let summary = |&(name, first, rest): &(&[u8], &Entry, &[Entry])| {
+            let columns = std::iter::once(first).chain(rest);
+            let stats = columns.map(|e| (e.min, e.sum, e.count, e.max));
+            (name.to_vec(), stats.collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            summary(&entries[0]),
+            (
+                b"Elsewhere".to_vec(),
+                vec![(1, 1, 1, 1), (2, 2, 1, 2), (3, 3, 1, 3)]
+            )
+        );
+        assert_eq!(
+            summary(&entries[1]),
+            (
+                b"Station 1".to_vec(),
+                vec![(1, 66, 4, 42), (-21, -22, 4, 0), (-100, 22, 4, 121)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut a = Histogram::default();
+        assert_eq!(a.quantile(0.5), None);
+
+        for temp in [5, -3, 5, 12, 0] {
+            a.record(temp);
+        }
+        let mut b = Histogram::default();
+        for temp in [-20, 30] {
+            b.record(temp);
+        }
+        a.merge(&b);
+        a.merge(&Histogram::default());
+
+        // Sorted: -20, -3, 0, 5, 5, 12, 30.
+        assert_eq!(a.count(), 7);
+        assert_eq!(a.quantile(0.0), Some(-20));
+        assert_eq!(a.quantile(0.5), Some(5));
+        assert_eq!(a.quantile(0.9), Some(30));
+        assert_eq!(a.quantile(1.0), Some(30));
+
+        // The lower median for even counts.
+        let mut c = Histogram::default();
+        for temp in [4, 1, 3, 2] {
+            c.record(temp);
+        }
+        assert_eq!(c.quantile(0.5), Some(2));
+    }
+
+    #[test]
+    fn test_entry_mean() {
+        let mean = |sum: i64, count: u64| {
+            Entry::<i16> {
+                sum,
+                count,
+                ..Entry::default()
+            }
+            .mean()
+        };
+
+        // Every tie from -99.95 to 99.95 rounds up, as `Math.round` does, and anything just
+        // either side of it rounds to the nearest unit.
+        for units in -1000..1000i64 {
+            for half in [1, 3, 1_000_003] {
+                let count = 2 * half;
+                let tie = (2 * units + 1) * half as i64;
+                assert_eq!(mean(tie, count), units + 1, "{tie} / {count}");
+                assert_eq!(mean(tie - 1, count), units, "{} / {count}", tie - 1);
+                assert_eq!(mean(tie + 1, count), units + 1, "{} / {count}", tie + 1);
+            }
+        }
+
+        assert_eq!(mean(-1, 2), 0);
+        assert_eq!(mean(-25, 2), -12);
+        assert_eq!(mean(-1251, 100), -13);
+        // Past 53 bits, where `sum as f64 / count as f64` would lose the tie.
+        assert_eq!(mean((1 << 54) + 1, 2), (1 << 53) + 1);
+        assert_eq!(mean(i64::MIN, 1), i64::MIN);
+        assert_eq!(mean(i64::MAX, 2), i64::MAX / 2 + 1);
+    }
+
+    #[test]
+    fn test_entry_variance() {
+        let mut entry = Entry::<i32>::default();
+        for temp in [1, 2, 3, 4] {
+            entry.record(temp);
+        }
+        assert_eq!(entry.variance(30), 1.25);
+
+        entry.record(-10);
+        assert_eq!(entry.variance(130), 26.0);
+
+        // Past 128 bits, it falls back to floats: `count * squares` is around 2^36 * 2^36 * 10^18.
+        let entry = Entry::<i32> {
+            sum: 0,
+            count: 1 << 36,
+            ..Entry::default()
+        };
+        assert_eq!(entry.variance(10u128.pow(18) << 36), 1e18);
+
+        // The widest temperatures, 9 digits, over as many rows as their sum can hold: exact, as
+        // `count * squares` is just under 2^126.
+        let max = 999_999_999i64;
+        let count = (i64::MAX / max) as u64;
+        let squares = count as u128 * (max * max) as u128;
+        assert!((count as u128).checked_mul(squares).unwrap() > 1 << 125);
+
+        let all_max = Entry::<i32> {
+            sum: max * count as i64,
+            count,
+            ..Entry::default()
+        };
+        assert_eq!(all_max.variance(squares), 0.0);
+
+        let alternating = Entry::<i32> {
+            sum: 0,
+            count: count - 1,
+            ..Entry::default()
+        };
+        let squares = (count - 1) as u128 * (max * max) as u128;
+        let variance = (max * max) as f64;
+        assert!((alternating.variance(squares) - variance).abs() <= variance * 1e-15);
+
+        // Squares too small for the sum can't come from the same temperatures, but don't wrap.
+        assert_eq!(all_max.variance(0), 0.0);
+        assert_eq!(all_max.variance(squares / 2), 0.0);
+    }
+
+    #[test]
+    fn test_update_tracking() {
+        let tracking = Tracking {
+            histograms: true,
+            squares: true,
+        };
+        let mut a = Table::with_tracking(16, 2, tracking);
+        let mut b = Table::with_tracking(16, 2, tracking);
+        let keys: Vec<String> = (0..40).map(|i| format!("Station {}", i % 20)).collect();
+
+        let insert = |tbl: &mut Table, key: &[u8], temps: &[i16]| {
+            let (hash, prefix) = Table::hash(key);
+            let slot = tbl.lookup_exact(hash, prefix, key);
+            tbl.update_columns(slot, hash, prefix, key, temps);
+        };
+
+        // Enough names to grow both tables, so tracked stats are moved along with their slots.
+        for (i, key) in keys.iter().enumerate() {
+            let i = i as i16;
+            insert(&mut a, key.as_bytes(), &[i, -i]);
+            insert(&mut b, key.as_bytes(), &[2 * i, 7]);
+        }
+        insert(&mut b, b"Elsewhere", &[1, 2]);
+
+        a.merge(&b);
+        a.retain(|name, _| name != b"Station 0");
+
+        let mut histograms = a.entries_histograms();
+        histograms.sort_unstable_by_key(|&(name, _)| name);
+        assert_eq!(histograms.len(), 20);
+
+        let (name, columns) = histograms[1];
+        assert_eq!(name, b"Station 1");
+        // Station 1 saw 1, 21, 2 and 42 in its first column, and -1, -21, 7, 7 in its second.
+        assert_eq!(columns[0].count(), 4);
+        assert_eq!(columns[0].quantile(0.5), Some(2));
+        assert_eq!(columns[0].quantile(1.0), Some(42));
+        assert_eq!(columns[1].quantile(0.25), Some(-21));
+        assert_eq!(columns[1].quantile(0.75), Some(7));
+
+        let mut squares = a.entries_squares();
+        squares.sort_unstable_by_key(|&(name, _)| name);
+        assert_eq!(squares[1], (&b"Station 1"[..], &[2210, 540][..]));
+
+        let mut plain = Table::with_columns(16, 2);
+        insert(&mut plain, b"Untracked", &[1, 2]);
+        assert_eq!(
+            plain.entries_histograms(),
+            vec![(&b"Untracked"[..], &[][..])]
+        );
+        assert_eq!(plain.entries_squares(), vec![(&b"Untracked"[..], &[][..])]);
+    }
+
+    #[test]
+    fn test_insert() {
+        let tracking = Tracking {
+            histograms: true,
+            squares: true,
+        };
+        let mut tbl = Table::with_tracking(16, 2, tracking);
+        for i in 0..40i16 {
+            let key = format!("Station {}", i % 20);
+            let (hash, prefix) = Table::hash(key.as_bytes());
+            let slot = tbl.lookup_exact(hash, prefix, key.as_bytes());
+            tbl.update_columns(slot, hash, prefix, key.as_bytes(), &[i, -i]);
+        }
+
+        let sorted = |tbl: &Table| {
+            let mut columns = tbl.entries_columns();
+            let mut histograms = tbl.entries_histograms();
+            let mut squares = tbl.entries_squares();
+            columns.sort_unstable_by_key(|&(name, _, _)| name);
+            histograms.sort_unstable_by_key(|&(name, _)| name);
+            squares.sort_unstable_by_key(|&(name, _)| name);
+            let columns = columns
+                .into_iter()
+                .map(|(name, first, rest)| (name, std::iter::once(first).chain(rest).cloned()));
+            columns
+                .zip(histograms.into_iter().zip(squares))
+                .map(|((name, c), ((_, h), (_, s)))| {
+                    (name.to_vec(), c.collect(), h.to_vec(), s.to_vec())
+                })
+                .collect::<Vec<(Vec<u8>, Vec<Entry>, Vec<Histogram>, Vec<u128>)>>()
+        };
+
+        // Enough names to grow the copy as they're inserted.
+        let mut copy = Table::with_tracking(16, 2, tracking);
+        for (name, columns, histograms, squares) in sorted(&tbl) {
+            copy.insert(&name, &columns, &histograms, &squares);
+        }
+        assert_eq!(sorted(&copy), sorted(&tbl));
+
+        // Inserting a name again combines its stats, as `merge` would.
+        tbl.merge(&copy);
+        for (name, columns, histograms, squares) in sorted(&copy) {
+            copy.insert(&name, &columns, &histograms, &squares);
+        }
+        assert_eq!(sorted(&copy), sorted(&tbl));
+
+        let station = &sorted(&copy)[1];
+        assert_eq!(station.0, b"Station 1");
+        assert_eq!((station.1[0].count, station.1[0].sum), (4, 44));
+        // Twice -1 and -21 in its second column.
+        let (lo, counts) = station.2[1].buckets();
+        assert_eq!((lo, counts.len(), counts[0], counts[20]), (-21, 21, 2, 2));
+        assert_eq!(station.3, [884, 884]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut tbl = Table::new(16);
+        let keys: Vec<String> = (0..40).map(|i| format!("Station {i}")).collect();
+
+        // Enough names to grow the table, so kept entries are rehashed into a new layout.
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key.as_bytes());
+            let slot = tbl.lookup_exact(hash, prefix, key.as_bytes());
+            tbl.update(slot, hash, prefix, key.as_bytes(), i as i16);
+        }
+
+        tbl.retain(|_, entry| entry.sum % 3 != 0);
+
+        let mut names: Vec<_> = tbl.entries().into_iter().map(|(name, _)| name).collect();
+        names.sort_unstable();
+        let mut expected: Vec<_> = (0..40)
+            .filter(|i| i % 3 != 0)
+            .map(|i| keys[i].as_bytes())
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+
+        for (i, key) in keys.iter().enumerate() {
+            let (hash, prefix) = Table::hash(key.as_bytes());
+            let slot = tbl.lookup_exact(hash, prefix, key.as_bytes());
+            let entry = &tbl.data[slot];
+
+            if i % 3 != 0 {
+                assert_eq!((entry.len as usize, entry.sum), (key.len(), i as i64));
+            } else {
+                assert_eq!(entry.len, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_beyond_u16_count() {
+        let mut tbl = Table::new(16);
+
+        let key = "Cardinal".as_bytes();
+        let (hash, prefix) = Table::hash(key);
+
+        for _ in 0..70_000 {
+            let slot = tbl.lookup(hash, prefix);
+            tbl.update(slot, hash, prefix, key, -12);
+        }
+
+        let slot = tbl.lookup(hash, prefix);
+        assert_eq!(tbl.data[slot].count, 70_000);
+        assert_eq!(tbl.data[slot].sum, -12 * 70_000);
+        assert_eq!(tbl.data[slot].min, -12);
+        assert_eq!(tbl.data[slot].max, -12);
+    }
+
+    #[test]
+    fn test_update_beyond_i32_sum() {
+        let mut tbl = Table::new(16);
+
+        let key = "Greater Manchester".as_bytes();
+        let (hash, prefix) = Table::hash(key);
+        let slot = tbl.lookup(hash, prefix);
+
+        let rows = (i32::MAX as u64 / 999) + 1_000;
+        for _ in 0..rows {
+            tbl.update(slot, hash, prefix, key, 999);
+        }
+
+        assert_eq!(tbl.data[slot].count, rows);
+        assert_eq!(tbl.data[slot].sum, 999 * rows as i64);
+        assert!(tbl.data[slot].sum > i32::MAX as i64);
+    }
 }
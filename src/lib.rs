@@ -1,2 +1,12 @@
+#![cfg_attr(feature = "portable-simd", feature(portable_simd))]
+
 pub mod byte_buffer;
+pub mod engine;
+pub mod error;
 pub mod hash_table;
+pub mod output;
+
+pub use error::Error;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,713 @@
+use crate::engine::{Columns, Precision, Results};
+use crate::hash_table::{Entry, Histogram, Tracking};
+use std::io::{self, Write};
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+/// Percentiles reported for each value column when histograms are tracked.
+pub const PERCENTILES: [(&str, f64); 3] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+/// What every station reports, for formats with a header or a fixed shape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// Fields in each station's key: more than one for composite keys.
+    pub key_fields: usize,
+    /// Value columns per station.
+    pub columns: usize,
+    pub tracking: Tracking,
+    pub precision: Precision,
+}
+
+impl Layout {
+    pub fn of(results: &Results) -> Self {
+        Self {
+            key_fields: results.key_columns(),
+            columns: results.columns(),
+            tracking: results.tracking(),
+            precision: results.precision(),
+        }
+    }
+
+    /// Names of the stats `Column::stats` reports, in order.
+    fn stat_names(&self) -> impl Iterator<Item = &'static str> + use<> {
+        let percentiles: &'static [_] = &PERCENTILES;
+        let percentiles = match self.tracking.histograms {
+            true => percentiles,
+            false => &[],
+        };
+        let spread: &'static [_] = match self.tracking.squares {
+            true => &["stddev", "variance"],
+            false => &[],
+        };
+
+        ["min", "mean", "max"]
+            .into_iter()
+            .chain(percentiles.iter().map(|&(name, _)| name))
+            .chain(spread.iter().copied())
+    }
+
+    /// Bytes `render` is likely to need for `entries` with `formatter`: each name, plus the
+    /// widest temperature for each stat and the count. Sized up front so the buffer rarely
+    /// grows.
+    fn capacity(&self, formatter: &dyn Formatter, entries: &[(&[u8], Columns)]) -> usize {
+        let digits = (self.precision.int_digits + self.precision.decimals) as usize;
+        let stats = self.stat_names().count() + 1;
+        let per_station = 8 + self.columns * stats * (digits + 2 + formatter.stat_overhead());
+        let names: usize = entries.iter().map(|(name, _)| name.len()).sum();
+
+        256 + names + entries.len() * per_station
+    }
+}
+
+/// One station: its key's fields, and each value column's stats.
+pub struct Station<'a> {
+    pub key: &'a [&'a [u8]],
+    pub columns: &'a [Column<'a>],
+}
+
+pub struct Column<'a> {
+    pub entry: Entry<i32>,
+    /// Set if histograms are tracked.
+    pub histogram: Option<&'a Histogram>,
+    /// Set if sums of squares are tracked.
+    pub squares: Option<u128>,
+}
+
+impl Column<'_> {
+    /// The stats named by `Layout::stat_names`: min, mean and max, then any percentiles, then
+    /// the standard deviation and variance if tracked.
+    fn stats(&self, precision: Precision) -> impl Iterator<Item = Stat> + '_ {
+        let entry = &self.entry;
+        let percentiles = self.histogram.into_iter().flat_map(|histogram| {
+            PERCENTILES.map(|(_, q)| Stat::Exact(histogram.quantile(q).unwrap() as i64))
+        });
+        let spread = self.squares.into_iter().flat_map(move |squares| {
+            let variance = precision.to_degrees_squared(entry.variance(squares));
+            [Stat::Real(variance.sqrt()), Stat::Real(variance)]
+        });
+
+        [entry.min as i64, entry.mean(), entry.max as i64]
+            .map(Stat::Exact)
+            .into_iter()
+            .chain(percentiles)
+            .chain(spread)
+    }
+}
+
+/// A stat in degrees, with the precision's decimal places.
+enum Stat {
+    /// A whole number of units, formatted exactly.
+    Exact(i64),
+    /// A derived stat in degrees, rounded half up to the precision's decimal places, as means
+    /// are. Never negative.
+    Real(f64),
+}
+
+impl Stat {
+    fn push(self, out: &mut Vec<u8>, precision: Precision) {
+        match self {
+            Stat::Exact(units) => push_degrees(out, units, precision.decimals),
+            Stat::Real(value) => {
+                let units = value * 10f64.powi(precision.decimals as i32);
+                push_degrees(out, (units + 0.5).floor() as i64, precision.decimals);
+            }
+        }
+    }
+}
+
+/// Renders stations one at a time into a byte buffer, in the order `render` passes them.
+pub trait Formatter {
+    /// Renders anything that comes before the first station.
+    fn begin(&self, out: &mut Vec<u8>, layout: &Layout);
+
+    /// Renders the `i`th station.
+    fn station(&self, out: &mut Vec<u8>, layout: &Layout, i: usize, station: &Station);
+
+    /// Renders anything that comes after the last station.
+    fn end(&self, out: &mut Vec<u8>, layout: &Layout);
+
+    /// Bytes each stat takes besides its digits, such as separators and names, for sizing the
+    /// buffer.
+    fn stat_overhead(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The 1BRC `{name=min/mean/max, ...}` line. Composite keys' fields are separated by `|`,
+    /// each value column's stats by spaces, and any extra stats follow `max`, separated by `/`.
+    Text,
+    /// A JSON object keyed by station, with composite keys' fields joined by `|`. Each maps to
+    /// its stats, or an array of them for several value columns.
+    Json,
+    /// CSV with a header. Composite keys take a column per field, and several value columns
+    /// suffix their stats with the column's index.
+    Csv,
+    /// A JSON object per station per line, with a `key` array for composite keys and a
+    /// `columns` array for several value columns.
+    Ndjson,
+}
+
+impl Format {
+    pub fn formatter(self) -> &'static dyn Formatter {
+        match self {
+            Format::Text => &Text,
+            Format::Json => &Json,
+            Format::Csv => &Csv,
+            Format::Ndjson => &Ndjson,
+        }
+    }
+}
+
+/// Renders every station in `results` with `formatter`, sorted by name, into one buffer.
+/// Temperatures and counts are rendered with integer arithmetic, and nothing is allocated per
+/// station, besides replacing invalid UTF-8 in JSON names.
+pub fn render(results: &Results, formatter: &dyn Formatter) -> Vec<u8> {
+    let layout = Layout::of(results);
+    let entries = results.entries_columns();
+    let histograms = results.histograms();
+    let squares = results.squares();
+
+    let mut out = Vec::with_capacity(layout.capacity(formatter, &entries));
+    let mut key = Vec::with_capacity(layout.key_fields);
+    let mut columns = Vec::with_capacity(layout.columns);
+
+    formatter.begin(&mut out, &layout);
+    for (i, &(name, entries)) in entries.iter().enumerate() {
+        key.clear();
+        key.extend(results.key_fields(name));
+
+        columns.clear();
+        columns.extend(entries.iter().enumerate().map(|(j, entry)| Column {
+            entry,
+            histogram: histograms.get(i).map(|(_, histograms)| &histograms[j]),
+            squares: squares.get(i).map(|(_, squares)| squares[j]),
+        }));
+
+        let station = Station {
+            key: &key,
+            columns: &columns,
+        };
+        formatter.station(&mut out, &layout, i, &station);
+    }
+    formatter.end(&mut out, &layout);
+
+    out
+}
+
+/// Renders `results` with `formatter` and writes them to `out` in one go.
+pub fn write(results: &Results, formatter: &dyn Formatter, out: &mut dyn Write) -> io::Result<()> {
+    out.write_all(&render(results, formatter))
+}
+
+pub struct Text;
+
+impl Formatter for Text {
+    fn begin(&self, out: &mut Vec<u8>, _: &Layout) {
+        out.push(b'{');
+    }
+
+    fn station(&self, out: &mut Vec<u8>, layout: &Layout, i: usize, station: &Station) {
+        if i != 0 {
+            out.extend_from_slice(b", ");
+        }
+        push_joined(out, station.key, b'|');
+        out.push(b'=');
+
+        for (j, column) in station.columns.iter().enumerate() {
+            if j != 0 {
+                out.push(b' ');
+            }
+            for (k, stat) in column.stats(layout.precision).enumerate() {
+                if k != 0 {
+                    out.push(b'/');
+                }
+                stat.push(out, layout.precision);
+            }
+        }
+    }
+
+    fn end(&self, out: &mut Vec<u8>, _: &Layout) {
+        out.extend_from_slice(b"}\n");
+    }
+}
+
+pub struct Json;
+
+impl Formatter for Json {
+    fn begin(&self, out: &mut Vec<u8>, _: &Layout) {
+        out.push(b'{');
+    }
+
+    fn station(&self, out: &mut Vec<u8>, layout: &Layout, i: usize, station: &Station) {
+        out.extend_from_slice(if i != 0 { b",\n  " } else { b"\n  " });
+        push_json_str(out, station.key, b'|');
+        out.extend_from_slice(b": ");
+        push_json_columns(out, layout, station.columns);
+    }
+
+    fn end(&self, out: &mut Vec<u8>, _: &Layout) {
+        out.extend_from_slice(b"\n}\n");
+    }
+
+    fn stat_overhead(&self) -> usize {
+        12
+    }
+}
+
+pub struct Csv;
+
+impl Formatter for Csv {
+    fn begin(&self, out: &mut Vec<u8>, layout: &Layout) {
+        match layout.key_fields {
+            1 => out.extend_from_slice(b"station"),
+            n => {
+                for i in 0..n {
+                    if i != 0 {
+                        out.push(b',');
+                    }
+                    out.extend_from_slice(b"key_");
+                    push_u64(out, i as u64);
+                }
+            }
+        }
+
+        for j in 0..layout.columns {
+            // The count follows the max.
+            let names = layout.stat_names().take(3).chain(["count"]);
+            for name in names.chain(layout.stat_names().skip(3)) {
+                out.push(b',');
+                out.extend_from_slice(name.as_bytes());
+                if layout.columns != 1 {
+                    out.push(b'_');
+                    push_u64(out, j as u64);
+                }
+            }
+        }
+
+        out.push(b'\n');
+    }
+
+    fn station(&self, out: &mut Vec<u8>, layout: &Layout, _: usize, station: &Station) {
+        for (i, field) in station.key.iter().enumerate() {
+            if i != 0 {
+                out.push(b',');
+            }
+            push_csv_field(out, field);
+        }
+
+        for column in station.columns {
+            for (k, stat) in column.stats(layout.precision).enumerate() {
+                out.push(b',');
+                stat.push(out, layout.precision);
+                if k == 2 {
+                    out.push(b',');
+                    push_u64(out, column.entry.count);
+                }
+            }
+        }
+
+        out.push(b'\n');
+    }
+
+    fn end(&self, _: &mut Vec<u8>, _: &Layout) {}
+}
+
+pub struct Ndjson;
+
+impl Formatter for Ndjson {
+    fn begin(&self, _: &mut Vec<u8>, _: &Layout) {}
+
+    fn station(&self, out: &mut Vec<u8>, layout: &Layout, _: usize, station: &Station) {
+        match station.key {
+            [name] => {
+                out.extend_from_slice(b"{\"station\":");
+                push_json_str(out, &[name], b'|');
+            }
+            fields => {
+                out.extend_from_slice(b"{\"key\":[");
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push(b',');
+                    }
+                    push_json_str(out, &[field], b'|');
+                }
+                out.push(b']');
+            }
+        }
+
+        match station.columns {
+            [column] => push_json_stats(out, layout, column, true),
+            columns => {
+                out.extend_from_slice(b",\"columns\":");
+                push_json_columns(out, layout, columns);
+            }
+        }
+        out.extend_from_slice(b"}\n");
+    }
+
+    fn end(&self, _: &mut Vec<u8>, _: &Layout) {}
+
+    fn stat_overhead(&self) -> usize {
+        12
+    }
+}
+
+/// A JSON object of the column's stats, or an array of them for several columns.
+fn push_json_columns(out: &mut Vec<u8>, layout: &Layout, columns: &[Column]) {
+    if let [column] = columns {
+        out.push(b'{');
+        push_json_stats(out, layout, column, false);
+        out.push(b'}');
+        return;
+    }
+
+    out.push(b'[');
+    for (j, column) in columns.iter().enumerate() {
+        out.extend_from_slice(if j != 0 { b",{" } else { b"{" });
+        push_json_stats(out, layout, column, false);
+        out.push(b'}');
+    }
+    out.push(b']');
+}
+
+/// The column's stats as JSON members, after a comma if `leading_comma`.
+fn push_json_stats(out: &mut Vec<u8>, layout: &Layout, column: &Column, leading_comma: bool) {
+    let stats = layout.stat_names().zip(column.stats(layout.precision));
+
+    // The count follows the max.
+    for (k, (name, stat)) in stats.enumerate() {
+        if k != 0 || leading_comma {
+            out.push(b',');
+        }
+        out.push(b'"');
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(b"\":");
+        stat.push(out, layout.precision);
+
+        if k == 2 {
+            out.extend_from_slice(b",\"count\":");
+            push_u64(out, column.entry.count);
+        }
+    }
+}
+
+/// Fields joined by `separator`.
+fn push_joined(out: &mut Vec<u8>, fields: &[&[u8]], separator: u8) {
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            out.push(separator);
+        }
+        out.extend_from_slice(field);
+    }
+}
+
+/// A JSON string of `fields` joined by `separator`, with invalid UTF-8 replaced.
+fn push_json_str(out: &mut Vec<u8>, fields: &[&[u8]], separator: u8) {
+    out.push(b'"');
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            out.push(separator);
+        }
+
+        // Only allocates for invalid UTF-8. Multi-byte characters never hold bytes below 0x80.
+        for &b in String::from_utf8_lossy(field).as_bytes() {
+            match b {
+                b'"' => out.extend_from_slice(b"\\\""),
+                b'\\' => out.extend_from_slice(b"\\\\"),
+                b'\n' => out.extend_from_slice(b"\\n"),
+                b'\r' => out.extend_from_slice(b"\\r"),
+                b'\t' => out.extend_from_slice(b"\\t"),
+                b if b < b' ' => {
+                    const HEX: &[u8; 16] = b"0123456789abcdef";
+                    out.extend_from_slice(b"\\u00");
+                    out.extend_from_slice(&[HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]);
+                }
+                b => out.push(b),
+            }
+        }
+    }
+    out.push(b'"');
+}
+
+/// A CSV field, quoted if it holds a comma, quote or line break.
+fn push_csv_field(out: &mut Vec<u8>, field: &[u8]) {
+    if !field
+        .iter()
+        .any(|b| matches!(b, b',' | b'"' | b'\n' | b'\r'))
+    {
+        out.extend_from_slice(field);
+        return;
+    }
+
+    out.push(b'"');
+    for chunk in field.split_inclusive(|&b| b == b'"') {
+        out.extend_from_slice(chunk);
+        if chunk.ends_with(b"\"") {
+            out.push(b'"');
+        }
+    }
+    out.push(b'"');
+}
+
+/// `n` in decimal.
+fn push_u64(out: &mut Vec<u8>, mut n: u64) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+
+    out.extend_from_slice(&digits[i..]);
+}
+
+/// `units` of `10^-decimals` degrees, in degrees with exactly `decimals` places, as
+/// `Precision::degrees` displays them.
+fn push_degrees(out: &mut Vec<u8>, units: i64, decimals: u32) {
+    // A sign, 19 digits and a point, plus up to 9 leading zeros for small values.
+    let mut digits = [0u8; 32];
+    let mut i = digits.len();
+    let mut abs = units.unsigned_abs();
+
+    for _ in 0..decimals {
+        i -= 1;
+        digits[i] = b'0' + (abs % 10) as u8;
+        abs /= 10;
+    }
+
+    i -= 1;
+    digits[i] = b'.';
+
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (abs % 10) as u8;
+        abs /= 10;
+        if abs == 0 {
+            break;
+        }
+    }
+
+    if units < 0 {
+        i -= 1;
+        digits[i] = b'-';
+    }
+
+    out.extend_from_slice(&digits[i..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{self, Config, Schema};
+    use std::{collections::BTreeMap, env, fs, path::Path};
+
+    const FORMATS: [(Format, &str); 4] = [
+        (Format::Text, "txt"),
+        (Format::Json, "json"),
+        (Format::Csv, "csv"),
+        (Format::Ndjson, "ndjson"),
+    ];
+
+    /// Compares each format's output against `tests/golden/{name}.*`. Set `UPDATE_GOLDEN` to
+    /// rewrite them instead.
+    fn check_golden(name: &str, results: &Results) {
+        for (format, extension) in FORMATS {
+            let mut actual = Vec::new();
+            write(results, format.formatter(), &mut actual).unwrap();
+            let actual = String::from_utf8(actual).unwrap();
+
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/golden")
+                .join(format!("{name}.{extension}"));
+            if env::var_os("UPDATE_GOLDEN").is_some() {
+                fs::write(&path, &actual).unwrap();
+                continue;
+            }
+
+            let expected = fs::read_to_string(&path).unwrap();
+            assert_eq!(actual, expected, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_push_degrees() {
+        for decimals in 1..=9 {
+            let precision = Precision {
+                decimals,
+                int_digits: 1,
+            };
+            let units = (-2_000..2_000).chain([i64::MIN, i64::MIN + 1, i64::MAX, 123_456_789]);
+
+            for units in units {
+                let mut out = Vec::new();
+                push_degrees(&mut out, units, decimals);
+                assert_eq!(out, precision.degrees(units).to_string().into_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_u64() {
+        for n in [0, 7, 10, 99, 100, 12_345, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            push_u64(&mut out, n);
+            assert_eq!(out, n.to_string().into_bytes());
+        }
+    }
+
+    #[test]
+    fn test_push_escaped() {
+        let mut out = Vec::new();
+        push_json_str(
+            &mut out,
+            &[b"a\"b\\c", b"\x01\x1f\t\xff", "Zürich".as_bytes()],
+            b'|',
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#""a\"b\\c|\u0001\u001f\t�|Zürich""#
+        );
+
+        let mut out = Vec::new();
+        for field in [&b"plain"[..], b"a,b", b"say \"hi\"", b"two\nlines"] {
+            push_csv_field(&mut out, field);
+            out.push(b' ');
+        }
+        assert_eq!(out, b"plain \"a,b\" \"say \"\"hi\"\"\" \"two\nlines\" ");
+    }
+
+    #[test]
+    fn test_render_capacity() {
+        let rows = (0..3_000).map(|i| {
+            let name = format!("Station {}", i % 1_000);
+            format!(
+                "{name};2024-0{};-{}.{};{}.5",
+                i % 9 + 1,
+                i % 100,
+                i % 10,
+                i % 50
+            )
+        });
+        let input = rows.collect::<Vec<_>>().join("\n");
+
+        let configs = [
+            Config {
+                schema: Schema {
+                    columns: 4,
+                    keys: 1 << 0,
+                    values: 1 << 2,
+                },
+                ..Config::default()
+            },
+            Config {
+                schema: Schema {
+                    columns: 4,
+                    keys: 1 << 0 | 1 << 1,
+                    values: 1 << 2 | 1 << 3,
+                },
+                tracking: Tracking {
+                    histograms: true,
+                    squares: true,
+                },
+                ..Config::default()
+            },
+        ];
+
+        // The buffer is sized up front, and never grows.
+        for config in configs {
+            let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+            let layout = Layout::of(&results);
+            let entries = results.entries_columns();
+
+            for (format, _) in FORMATS {
+                let formatter = format.formatter();
+                let out = render(&results, formatter);
+                assert!(out.len() <= out.capacity(), "{format:?}");
+                assert_eq!(
+                    out.capacity(),
+                    layout.capacity(formatter, &entries),
+                    "{format:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_rounding() {
+        // Two rows per station, whose mean is each tie from -99.85 to 99.85, `.x5` in degrees.
+        let tenths = |t: i32| format!("{:.1}", t as f64 / 10.0);
+        let mut input = String::new();
+        let mut expected = BTreeMap::new();
+        for units in -999..=998 {
+            let name = format!("Station {units}");
+            input += &format!("{name};{}\n{name};{}\n", tenths(units), tenths(units + 1));
+
+            // Ties round up, as `Math.round` does in the reference.
+            let stats = [units, units + 1, units + 1].map(tenths);
+            expected.insert(name, stats.join("/"));
+        }
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(name, stats)| format!("{name}={stats}"))
+            .collect();
+
+        let results = engine::aggregate_with(input.as_bytes(), &Config::default()).unwrap();
+        let mut actual = Vec::new();
+        write(&results, &Text, &mut actual).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            format!("{{{}}}\n", expected.join(", "))
+        );
+
+        let input = "a;-0.1\na;0.0\nb;0.0\nb;0.1\nc;-1.2\nc;-1.3\nd;1.2\nd;1.3\n";
+        let results = engine::aggregate_with(input.as_bytes(), &Config::default()).unwrap();
+        let mut actual = Vec::new();
+        write(&results, &Text, &mut actual).unwrap();
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            "{a=-0.1/0.0/0.0, b=0.0/0.1/0.1, c=-1.3/-1.2/-1.2, d=1.2/1.3/1.3}\n"
+        );
+    }
+
+    #[test]
+    fn test_golden_plain() {
+        let input = "Hamburg;12.0\nBulawayo;8.9\nPalembang;38.8\nSt. John's;15.2\n\
+                     Hamburg;-3.4\n\"Quoted\", Town;-0.1\nZürich;22.5\nTab\tTown;0.0\n\
+                     Bulawayo;-12.3\nHamburg;34.2\n";
+
+        let results = engine::aggregate_with(input.as_bytes(), &Config::default()).unwrap();
+        check_golden("plain", &results);
+    }
+
+    #[test]
+    fn test_golden_tracked() {
+        let input = "Hamburg;2024-01;1.5;-3.0\nHamburg;2024-02;4.5;2.5\nHamburg;2024-01;-2.0;0.5\n\
+                     Oslo, Norway;2024-01;-9.9;-1.5\nHamburg;2024-01;7.5;1.0\n\
+                     Oslo, Norway;2024-01;-4.1;3.5\nHamburg;2024-02;3.5;-0.5\n";
+
+        let config = Config {
+            schema: Schema {
+                columns: 4,
+                keys: 1 << 0 | 1 << 1,
+                values: 1 << 2 | 1 << 3,
+            },
+            tracking: Tracking {
+                histograms: true,
+                squares: true,
+            },
+            ..Config::default()
+        };
+        let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+        check_golden("tracked", &results);
+    }
+}
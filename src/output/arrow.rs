@@ -0,0 +1,185 @@
+use crate::engine::Results;
+use crate::output::{Layout, PERCENTILES};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use std::io::Write;
+use std::sync::Arc;
+
+/// The schema of `record_batch` for results laid out as `layout`: the name, then each value
+/// column's `min`, `mean`, `max` and `count`, then `p50`, `p90` and `p99` with histograms, and
+/// `stddev` and `variance` with squares. With several value columns, each field is suffixed with
+/// its column's index, as in the CSV output.
+pub fn schema(layout: &Layout) -> Schema {
+    let mut fields = vec![Field::new("name", DataType::Utf8, false)];
+
+    for j in 0..layout.columns {
+        let suffix = match layout.columns {
+            1 => String::new(),
+            _ => format!("_{j}"),
+        };
+        // The count follows the max.
+        for (k, name) in layout.stat_names().enumerate() {
+            fields.push(Field::new(
+                format!("{name}{suffix}"),
+                DataType::Float64,
+                false,
+            ));
+            if k == 2 {
+                fields.push(Field::new(
+                    format!("count{suffix}"),
+                    DataType::UInt64,
+                    false,
+                ));
+            }
+        }
+    }
+
+    Schema::new(fields)
+}
+
+/// A row per station, sorted by name, with every value column. Temperatures are in degrees,
+/// converted from their exact units, and nothing is rounded: the mean is the real mean, where the
+/// text output rounds it half up. Composite keys' fields are joined by the separator.
+pub fn record_batch(results: &Results) -> Result<RecordBatch, ArrowError> {
+    let layout = Layout::of(results);
+    let entries = results.entries_columns();
+    let histograms = results.histograms();
+    let squares = results.squares();
+    let precision = layout.precision;
+    let scale = 10f64.powi(precision.decimals as i32);
+
+    let names = entries
+        .iter()
+        .map(|(name, _)| String::from_utf8_lossy(name));
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(names))];
+
+    for j in 0..layout.columns {
+        let column: Vec<_> = entries.iter().map(|(_, c)| c.column(j)).collect();
+
+        columns.push(floats(column.iter().map(|e| e.min as f64 / scale)));
+        columns.push(floats(
+            column.iter().map(|e| e.sum as f64 / e.count as f64 / scale),
+        ));
+        columns.push(floats(column.iter().map(|e| e.max as f64 / scale)));
+        let count = column.iter().map(|e| e.count);
+        columns.push(Arc::new(UInt64Array::from_iter_values(count)));
+
+        if layout.tracking.histograms {
+            for (_, q) in PERCENTILES {
+                let percentile = histograms
+                    .iter()
+                    .map(|(_, histograms)| histograms[j].quantile(q).unwrap() as f64 / scale);
+                columns.push(floats(percentile));
+            }
+        }
+
+        if layout.tracking.squares {
+            let variance: Vec<f64> = column
+                .iter()
+                .zip(&squares)
+                .map(|(entry, (_, squares))| {
+                    precision.to_degrees_squared(entry.variance(squares[j]))
+                })
+                .collect();
+            columns.push(floats(variance.iter().map(|variance| variance.sqrt())));
+            columns.push(Arc::new(Float64Array::from(variance)));
+        }
+    }
+
+    RecordBatch::try_new(Arc::new(schema(&layout)), columns)
+}
+
+fn floats(values: impl Iterator<Item = f64>) -> ArrayRef {
+    Arc::new(Float64Array::from_iter_values(values))
+}
+
+/// Writes `record_batch` to `out` as an Arrow IPC file.
+pub fn write_ipc(results: &Results, out: impl Write) -> Result<(), ArrowError> {
+    let batch = record_batch(results)?;
+    let mut writer = FileWriter::try_new(out, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{self, Config, Schema as RowSchema};
+    use arrow_array::Array;
+    use arrow_ipc::reader::FileReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_ipc() {
+        let input = "Hamburg;12.0\nBulawayo;8.9\nZürich;-1.2\nHamburg;-3.4\nZürich;-1.3\n";
+        let results = engine::aggregate_with(input.as_bytes(), &Config::default()).unwrap();
+
+        let mut out = Vec::new();
+        write_ipc(&results, &mut out).unwrap();
+
+        let mut reader = FileReader::try_new(Cursor::new(out), None).unwrap();
+        assert_eq!(*reader.schema(), schema(&Layout::of(&results)));
+        let batch = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+        assert_eq!(batch, record_batch(&results).unwrap());
+
+        let column = |name| batch.column_by_name(name).unwrap().as_any();
+        let names = column("name").downcast_ref::<StringArray>().unwrap();
+        let floats = |name| {
+            let array = column(name).downcast_ref::<Float64Array>().unwrap();
+            array.values().to_vec()
+        };
+        let count = column("count").downcast_ref::<UInt64Array>().unwrap();
+
+        assert_eq!(
+            names.iter().flatten().collect::<Vec<_>>(),
+            ["Bulawayo", "Hamburg", "Zürich"]
+        );
+        assert_eq!(floats("min"), [8.9, -3.4, -1.3]);
+        // Not rounded, unlike the text output's -1.2.
+        assert_eq!(floats("mean"), [8.9, 4.3, -1.25]);
+        assert_eq!(floats("max"), [8.9, 12.0, -1.2]);
+        assert_eq!(count.values().to_vec(), [1, 2, 2]);
+        assert_eq!(batch.column(0).null_count(), 0);
+    }
+
+    #[test]
+    fn test_record_batch_columns() {
+        let config = Config {
+            schema: RowSchema {
+                columns: 3,
+                keys: 1 << 0,
+                values: 1 << 1 | 1 << 2,
+            },
+            ..Config::default()
+        };
+        let input = "Hamburg;12.0;-1.0\nHamburg;-3.4;2.0\n";
+        let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+        let batch = record_batch(&results).unwrap();
+
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "name", "min_0", "mean_0", "max_0", "count_0", "min_1", "mean_1", "max_1",
+                "count_1"
+            ]
+        );
+        let floats = |name| {
+            let column = batch.column_by_name(name).unwrap().as_any();
+            column
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(floats("mean_0"), [4.3]);
+        assert_eq!(floats("mean_1"), [0.5]);
+    }
+}
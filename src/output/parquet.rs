@@ -0,0 +1,115 @@
+use crate::engine::Results;
+use crate::output::arrow::record_batch;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use std::io::Write;
+
+/// Writes `record_batch` to `out` as a Parquet file in a single row group, with the extended
+/// stats columns whenever the results track them.
+pub fn write_parquet(results: &Results, out: impl Write + Send) -> Result<(), ParquetError> {
+    let batch = record_batch(results)?;
+    let mut writer = ArrowWriter::try_new(out, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{self, Config, Schema};
+    use crate::hash_table::Tracking;
+    use crate::output::Layout;
+    use crate::output::arrow::schema;
+    use arrow_array::{Float64Array, RecordBatch, UInt64Array};
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    /// Writes `results` to an in-memory Parquet file and reads it back as one batch.
+    fn round_trip(results: &Results) -> RecordBatch {
+        let mut out = Vec::new();
+        write_parquet(results, &mut out).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(out))
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        batches.remove(0)
+    }
+
+    fn floats(batch: &RecordBatch, name: &str) -> Vec<f64> {
+        let column = batch.column_by_name(name).unwrap().as_any();
+        column
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let input = "Hamburg;12.0\nBulawayo;8.9\nZürich;-1.2\nHamburg;-3.4\nZürich;-1.3\n";
+        let config = Config {
+            tracking: Tracking {
+                histograms: true,
+                squares: true,
+            },
+            ..Config::default()
+        };
+        let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+
+        let batch = round_trip(&results);
+        assert_eq!(*batch.schema(), schema(&Layout::of(&results)));
+        assert_eq!(batch, record_batch(&results).unwrap());
+
+        assert_eq!(floats(&batch, "mean"), [8.9, 4.3, -1.25]);
+        assert_eq!(floats(&batch, "p50"), [8.9, -3.4, -1.3]);
+        assert_eq!(floats(&batch, "p99"), [8.9, 12.0, -1.2]);
+        assert_eq!(floats(&batch, "variance"), [0.0, 59.29, 0.0025]);
+        assert_eq!(floats(&batch, "stddev"), [0.0, 7.7, 0.05]);
+    }
+
+    #[test]
+    fn test_write_parquet_columns() {
+        let input = "Hamburg;1;12.0;x;-1.0;5.5\nOslo;2;-3.4;y;2.0;0.0\nHamburg;3;4.0;z;1.0;-5.5\n";
+        let config = Config {
+            schema: Schema {
+                columns: 6,
+                keys: 1 << 0,
+                values: 1 << 2 | 1 << 4 | 1 << 5,
+            },
+            tracking: Tracking {
+                histograms: false,
+                squares: true,
+            },
+            ..Config::default()
+        };
+        let results = engine::aggregate_with(input.as_bytes(), &config).unwrap();
+        let batch = round_trip(&results);
+        assert_eq!(batch, record_batch(&results).unwrap());
+
+        // Every value column comes back, suffixed with its index.
+        let expected = [
+            ([4.0, -3.4], [8.0, -3.4], [12.0, -3.4], [16.0, 0.0]),
+            ([-1.0, 2.0], [0.0, 2.0], [1.0, 2.0], [1.0, 0.0]),
+            ([-5.5, 0.0], [0.0, 0.0], [5.5, 0.0], [30.25, 0.0]),
+        ];
+        for (j, (min, mean, max, variance)) in expected.into_iter().enumerate() {
+            assert_eq!(floats(&batch, &format!("min_{j}")), min, "column {j}");
+            assert_eq!(floats(&batch, &format!("mean_{j}")), mean, "column {j}");
+            assert_eq!(floats(&batch, &format!("max_{j}")), max, "column {j}");
+            assert_eq!(
+                floats(&batch, &format!("variance_{j}")),
+                variance,
+                "column {j}"
+            );
+
+            let count = batch.column_by_name(&format!("count_{j}")).unwrap();
+            let count = count.as_any().downcast_ref::<UInt64Array>().unwrap();
+            assert_eq!(count.values().to_vec(), [2, 1], "column {j}");
+        }
+        assert_eq!(batch.num_columns(), 1 + 3 * 6);
+    }
+}
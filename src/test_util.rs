@@ -0,0 +1,54 @@
+use std::ptr;
+
+/// A copy of some bytes that ends exactly where an inaccessible guard page begins, so any read
+/// past the end faults.
+pub struct Guarded {
+    map: *mut u8,
+    map_len: usize,
+    start: *const u8,
+    len: usize,
+}
+
+impl Guarded {
+    pub fn new(data: &[u8]) -> Self {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let data_len = data.len().div_ceil(page) * page;
+        let map_len = data_len + page;
+
+        unsafe {
+            let map = libc::mmap(
+                ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(map, libc::MAP_FAILED, "mmap failed");
+
+            let map = map as *mut u8;
+            let guard = map.add(data_len);
+            assert_eq!(libc::mprotect(guard as *mut _, page, libc::PROT_NONE), 0);
+
+            let start = guard.sub(data.len());
+            ptr::copy_nonoverlapping(data.as_ptr(), start, data.len());
+
+            Self {
+                map,
+                map_len,
+                start,
+                len: data.len(),
+            }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.start, self.len) }
+    }
+}
+
+impl Drop for Guarded {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map as *mut _, self.map_len) };
+    }
+}